@@ -0,0 +1,213 @@
+//! Compares the priority queues of the searches: a plain binary heap where tiles reached through a
+//! cheaper path are inserted again and the stale entries are skipped when extracted (lazy
+//! deletion), against an indexed heap where the key of the tile is decreased instead. The searches
+//! go between the doorways of every corridor of generated grids over a value noise cost field, so
+//! that tiles are often reached through cheaper paths. Run with `cargo bench --bench heap`.
+
+use dungen::Configuration;
+use dungen::binary_heap::{Heap, IndexedHeap};
+use dungen::cost_field::value_noise;
+use dungen::grid::{Tile, make_grid};
+use dungen::mst::pick_corridors;
+use dungen::room::generate_rooms;
+use dungen::triangulation::triangulate;
+use dungen::vec::{self, vec2u};
+
+use rand::SeedableRng;
+use std::time::{Duration, Instant};
+
+const GRID_DIMENSION: usize = 512;
+const TARGET_ROOM_COUNT: usize = 2_000;
+const SEEDS: u64 = 5;
+
+/// What a single search reports.
+#[derive(Default)]
+struct Statistics {
+    cost: usize,
+    pushes: usize,
+    max_heap_size: usize,
+}
+
+/// Buffers of the searches. The scores are reset before every search in both variants so that
+/// only the priority queues differ.
+struct Search<'a> {
+    width: usize,
+    tiles: &'a [Tile],
+    costs: &'a [usize],
+    g_scores: Vec<usize>,
+}
+
+impl Search<'_> {
+    fn reset(&mut self) {
+        self.g_scores.clear();
+        self.g_scores.resize(self.tiles.len(), usize::MAX / 2);
+    }
+
+    fn neighbors(&self, tile: usize) -> impl Iterator<Item = usize> + '_ {
+        [tile + self.width, tile - self.width, tile + 1, tile - 1]
+            .into_iter()
+            .filter(|&neighbor| !matches!(self.tiles[neighbor], Tile::Blocker | Tile::Room))
+    }
+
+    fn heuristic(&self, from: usize, to: usize) -> usize {
+        (from % self.width).abs_diff(to % self.width)
+            + (from / self.width).abs_diff(to / self.width)
+    }
+
+    fn lazy(&mut self, heap: &mut Heap<usize, usize>, from: usize, to: usize) -> Statistics {
+        self.reset();
+        heap.clear();
+        let mut statistics = Statistics::default();
+        let mut size = 0;
+
+        self.g_scores[from] = 0;
+        heap.insert(self.heuristic(from, to), from);
+        size += 1;
+        statistics.pushes += 1;
+        while let Some((f_cost, current)) = heap.extract_min() {
+            size -= 1;
+            let g_cost = f_cost - self.heuristic(current, to);
+            if g_cost > self.g_scores[current] {
+                continue;
+            }
+            if current == to {
+                statistics.cost = g_cost;
+                break;
+            }
+            let neighbors: Vec<usize> = self.neighbors(current).collect();
+            for neighbor in neighbors {
+                let tentative_g_score = g_cost + 1 + self.costs[neighbor];
+                if tentative_g_score < self.g_scores[neighbor] {
+                    self.g_scores[neighbor] = tentative_g_score;
+                    heap.insert(tentative_g_score + self.heuristic(neighbor, to), neighbor);
+                    size += 1;
+                    statistics.pushes += 1;
+                    statistics.max_heap_size = statistics.max_heap_size.max(size);
+                }
+            }
+        }
+        statistics
+    }
+
+    fn indexed(&mut self, heap: &mut IndexedHeap<usize>, from: usize, to: usize) -> Statistics {
+        self.reset();
+        heap.clear();
+        let mut statistics = Statistics::default();
+
+        self.g_scores[from] = 0;
+        heap.insert(self.heuristic(from, to), from);
+        statistics.pushes += 1;
+        while let Some((_, current)) = heap.extract_min() {
+            let g_cost = self.g_scores[current];
+            if current == to {
+                statistics.cost = g_cost;
+                break;
+            }
+            let neighbors: Vec<usize> = self.neighbors(current).collect();
+            for neighbor in neighbors {
+                let tentative_g_score = g_cost + 1 + self.costs[neighbor];
+                if tentative_g_score < self.g_scores[neighbor] {
+                    self.g_scores[neighbor] = tentative_g_score;
+                    let f_score = tentative_g_score + self.heuristic(neighbor, to);
+                    if heap.contains(neighbor) {
+                        heap.decrease_key(neighbor, f_score);
+                    } else {
+                        heap.insert(f_score, neighbor);
+                        statistics.pushes += 1;
+                        statistics.max_heap_size = statistics.max_heap_size.max(heap.len());
+                    }
+                }
+            }
+        }
+        statistics
+    }
+}
+
+fn main() {
+    let configuration = Configuration::default();
+    let grid_dimensions = vec2u(GRID_DIMENSION, GRID_DIMENSION);
+
+    let mut lazy_time = Duration::ZERO;
+    let mut indexed_time = Duration::ZERO;
+    let mut lazy_total = Statistics::default();
+    let mut indexed_total = Statistics::default();
+    let mut search_count = 0;
+
+    for seed in 0..SEEDS {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut dungeon = generate_rooms(
+            &configuration,
+            grid_dimensions,
+            Some(TARGET_ROOM_COUNT),
+            &mut rng,
+        );
+        let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+        let corridors = pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+        let grid = match make_grid(&configuration, grid_dimensions, &dungeon, &corridors) {
+            Ok((grid, _)) => grid,
+            Err(error) => error.grid,
+        };
+        let costs = value_noise(grid_dimensions, 16, 20, &mut rng);
+
+        let searches: Vec<(usize, usize)> = corridors
+            .iter()
+            .map(|edge| {
+                (
+                    vec::to_index(dungeon.doorways[edge.0].position, grid.width),
+                    vec::to_index(dungeon.doorways[edge.1].position, grid.width),
+                )
+            })
+            .collect();
+        search_count += searches.len();
+
+        let mut search = Search {
+            width: grid.width,
+            tiles: &grid.tiles,
+            costs: &costs,
+            g_scores: vec![],
+        };
+
+        let mut heap = Heap::with_capacity(grid.tiles.len() / 4);
+        let start = Instant::now();
+        let lazy: Vec<Statistics> = searches
+            .iter()
+            .map(|&(from, to)| search.lazy(&mut heap, from, to))
+            .collect();
+        lazy_time += start.elapsed();
+
+        let mut heap = IndexedHeap::with_capacity(grid.tiles.len());
+        let start = Instant::now();
+        let indexed: Vec<Statistics> = searches
+            .iter()
+            .map(|&(from, to)| search.indexed(&mut heap, from, to))
+            .collect();
+        indexed_time += start.elapsed();
+
+        for (lazy, indexed) in lazy.iter().zip(&indexed) {
+            assert_eq!(
+                lazy.cost, indexed.cost,
+                "Both searches should find paths of equal cost."
+            );
+            lazy_total.pushes += lazy.pushes;
+            lazy_total.max_heap_size = lazy_total.max_heap_size.max(lazy.max_heap_size);
+            indexed_total.pushes += indexed.pushes;
+            indexed_total.max_heap_size = indexed_total.max_heap_size.max(indexed.max_heap_size);
+        }
+    }
+
+    println!(
+        "{SEEDS} grids of {GRID_DIMENSION}x{GRID_DIMENSION} tiles with {search_count} corridors"
+    );
+    println!(
+        "lazy deletion: {lazy_time:?}, {} insertions, largest heap {}",
+        lazy_total.pushes, lazy_total.max_heap_size
+    );
+    println!(
+        "decrease key:  {indexed_time:?}, {} insertions, largest heap {}",
+        indexed_total.pushes, indexed_total.max_heap_size
+    );
+    println!(
+        "speedup: {:.2}x",
+        lazy_time.as_secs_f64() / indexed_time.as_secs_f64()
+    );
+}
@@ -0,0 +1,211 @@
+//! Compares the cost of the corridor searches with lazily reset buffers against resetting the
+//! buffers over the whole grid before every search, which is what the pathfinding algorithm used to
+//! do. The old algorithm is kept below as the baseline, using the same indexed heap as the current
+//! one so that only the reset is compared. Run with `cargo bench --bench pathfinding`.
+
+use dungen::Configuration;
+use dungen::a_star::{Pathfinder, diff};
+use dungen::binary_heap::IndexedHeap;
+use dungen::grid::{Tile, make_grid};
+use dungen::mst::pick_corridors;
+use dungen::room::generate_rooms;
+use dungen::triangulation::triangulate;
+use dungen::vec::{self, vec2u};
+
+use rand::SeedableRng;
+use std::time::{Duration, Instant};
+
+const GRID_DIMENSION: usize = 512;
+const TARGET_ROOM_COUNT: usize = 2_000;
+const SEEDS: u64 = 5;
+
+/// Manhatan distance between two indices representing coordinates in a grid.
+fn manhattan(width: usize, a: usize, b: usize) -> usize {
+    diff(a / width, b / width) + diff(a % width, b % width)
+}
+
+/// Answers whether the four given nodes create a 2x2 square.
+fn make_square(width: usize, mut nodes: [usize; 4]) -> bool {
+    nodes.sort();
+    nodes[0] + 1 == nodes[1] && nodes[0] + width == nodes[2] && nodes[0] + width + 1 == nodes[3]
+}
+
+/// The pathfinding algorithm as it was before the lazy reset, which initializes the scores and the
+/// parents of the whole grid before every search. Only its heap has been changed to the indexed
+/// one.
+#[allow(clippy::too_many_arguments)]
+fn eager_a_star(
+    configuration: &Configuration,
+    start: usize,
+    end: usize,
+    width: usize,
+    tiles: &[Tile],
+    open_set: &mut IndexedHeap<usize>,
+    g_scores: &mut Vec<usize>,
+    parent: &mut Vec<usize>,
+    path: &mut Vec<usize>,
+) {
+    use Tile::*;
+
+    let disallow_corridor_squares = configuration.disallow_corridor_squares;
+    let corridor_cost = configuration.corridor_cost;
+    let straight_cost = configuration.straight_cost;
+    let standard_cost = configuration.standard_cost;
+    let min_cost = corridor_cost.min(straight_cost).min(standard_cost);
+
+    // Initialize the structures.
+    open_set.clear();
+    // Expect the grids to be small enough so that half of usize::MAX is effectively infinity.
+    g_scores.clear();
+    g_scores.resize(tiles.len(), usize::MAX / 2);
+    parent.clear();
+    for i in 0..tiles.len() {
+        parent.push(i);
+    }
+    path.clear();
+
+    g_scores[start] = 0;
+    open_set.insert(manhattan(width, start, end) * min_cost, start);
+
+    while let Some((_, mut current)) = open_set.extract_min() {
+        if current == end {
+            path.push(current);
+            while current != parent[current] {
+                current = parent[current];
+                path.push(current);
+            }
+            return;
+        }
+
+        for neighbor in [
+            current + width, // south
+            current - width, // north
+            current + 1,     // east
+            current - 1,     // west
+        ] {
+            if matches!(tiles[neighbor], Blocker | Room) {
+                continue;
+            }
+            if disallow_corridor_squares {
+                if matches!(tiles[current], CorridorNeighbor)
+                    && matches!(tiles[neighbor], CorridorNeighbor)
+                {
+                    continue;
+                }
+                if make_square(
+                    width,
+                    [neighbor, current, parent[current], parent[parent[current]]],
+                ) {
+                    continue;
+                }
+            }
+
+            let cost = if matches!(tiles[neighbor], Corridor) {
+                corridor_cost
+            } else if diff(current, parent[current]) == diff(neighbor, current) {
+                straight_cost
+            } else {
+                standard_cost
+            };
+
+            let tentative_g_score = g_scores[current] + cost;
+            if tentative_g_score < g_scores[neighbor] {
+                parent[neighbor] = current;
+                g_scores[neighbor] = tentative_g_score;
+                let f_score = tentative_g_score + manhattan(width, neighbor, end) * min_cost;
+                if open_set.contains(neighbor) {
+                    open_set.decrease_key(neighbor, f_score);
+                } else {
+                    open_set.insert(f_score, neighbor);
+                }
+            }
+        }
+    }
+}
+
+fn main() {
+    let configuration = Configuration::default();
+    let grid_dimensions = vec2u(GRID_DIMENSION, GRID_DIMENSION);
+
+    let mut grid_time = Duration::ZERO;
+    let mut lazy_time = Duration::ZERO;
+    let mut eager_time = Duration::ZERO;
+    let mut search_count = 0;
+
+    for seed in 0..SEEDS {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut dungeon = generate_rooms(
+            &configuration,
+            grid_dimensions,
+            Some(TARGET_ROOM_COUNT),
+            &mut rng,
+        );
+        let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+        let corridors = pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+
+        let start = Instant::now();
+        let grid = match make_grid(&configuration, grid_dimensions, &dungeon, &corridors) {
+            Ok((grid, _)) => grid,
+            Err(error) => error.grid,
+        };
+        grid_time += start.elapsed();
+
+        // Search again between the doorways of every corridor in the finished grid.
+        let searches: Vec<(usize, usize)> = corridors
+            .iter()
+            .map(|edge| {
+                (
+                    vec::to_index(dungeon.doorways[edge.0].position, grid.width),
+                    vec::to_index(dungeon.doorways[edge.1].position, grid.width),
+                )
+            })
+            .collect();
+        search_count += searches.len();
+
+        let mut pathfinder = Pathfinder::new(grid.tiles.len());
+        let start = Instant::now();
+        for &(from, to) in &searches {
+            std::hint::black_box(pathfinder.find_path(
+                &configuration,
+                from,
+                to,
+                grid.width,
+                &grid.tiles,
+            ));
+        }
+        lazy_time += start.elapsed();
+
+        // The same searches with the old algorithm.
+        let mut open_set = IndexedHeap::with_capacity(grid.tiles.len());
+        let mut g_scores: Vec<usize> = vec![];
+        let mut parent: Vec<usize> = vec![];
+        let mut path: Vec<usize> = vec![];
+        let start = Instant::now();
+        for &(from, to) in &searches {
+            eager_a_star(
+                &configuration,
+                from,
+                to,
+                grid.width,
+                &grid.tiles,
+                &mut open_set,
+                &mut g_scores,
+                &mut parent,
+                &mut path,
+            );
+            std::hint::black_box(&path);
+        }
+        eager_time += start.elapsed();
+    }
+
+    println!(
+        "{SEEDS} grids of {GRID_DIMENSION}x{GRID_DIMENSION} tiles with {search_count} corridors"
+    );
+    println!("grid generation:     {grid_time:?}");
+    println!("lazy reset searches: {lazy_time:?}");
+    println!("eager reset searches: {eager_time:?}");
+    println!(
+        "speedup: {:.2}x",
+        eager_time.as_secs_f64() / lazy_time.as_secs_f64()
+    );
+}
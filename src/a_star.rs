@@ -1,10 +1,10 @@
 use crate::Configuration;
-use crate::binary_heap::Heap;
+use crate::binary_heap::IndexedHeap;
 use crate::grid::Tile;
 
 /// Absolute difference between two unsigned integers
 pub fn diff(a: usize, b: usize) -> usize {
-    if a < b { b - a } else { a - b }
+    a.abs_diff(b)
 }
 
 /// Manhatan distance between two indices representing coordinates in a grid.
@@ -12,6 +12,26 @@ fn manhattan(width: usize, a: usize, b: usize) -> usize {
     diff(a / width, b / width) + diff(a % width, b % width)
 }
 
+/// Chebyshev distance between two indices representing coordinates in a grid, i.e. the number of
+/// steps needed when diagonal steps are allowed.
+fn chebyshev(width: usize, a: usize, b: usize) -> usize {
+    diff(a / width, b / width).max(diff(a % width, b % width))
+}
+
+/// Answers whether a square of tiles of the given size with its north-west tile at the anchor
+/// contains no rooms or blockers. The tiles are checked row by row, so the perimeter of blockers
+/// around the grid is found before going out of bounds.
+fn has_clearance(width: usize, tiles: &[Tile], anchor: usize, size: usize) -> bool {
+    (0..size).all(|row| {
+        (0..size).all(|column| {
+            !matches!(
+                tiles[anchor + row * width + column],
+                Tile::Blocker | Tile::Room
+            )
+        })
+    })
+}
+
 /// Answers whether the four given nodes create a 2x2 square.
 fn make_square(width: usize, mut nodes: [usize; 4]) -> bool {
     nodes.sort();
@@ -19,106 +39,239 @@ fn make_square(width: usize, mut nodes: [usize; 4]) -> bool {
 }
 
 /// A* pathfinding algorithm to carve corridors in the grid. It follows a couple of rules to
-/// guarantee that the corridors follow a specific shape. Since this procedure will be called
-/// multiple times, there is no need to allocate the needed structures more than once.
+/// guarantee that the corridors follow a specific shape. Since the search will be run once for
+/// every corridor, the structure owns the needed buffers so that they are allocated only once.
 ///
-/// The heuristic used is the manhatan distance from this cell to the end multiplied by the minimum
-/// cost (with respect of all types of cost). The heuristic is admissible and consistent as we
-/// assume the best case of traveling only through the cheapest possible path.
-#[allow(clippy::too_many_arguments)]
-pub fn a_star(
-    configuration: &Configuration,
-    start: usize,
-    end: usize,
-    width: usize,
-    tiles: &[Tile],
-    open_set: &mut Heap<usize, usize>,
-    g_scores: &mut Vec<usize>,
-    parent: &mut Vec<usize>,
-    path: &mut Vec<usize>,
-) {
-    use Tile::*;
-
-    let disallow_corridor_squares = configuration.disallow_corridor_squares;
-    let corridor_cost = configuration.corridor_cost;
-    let straight_cost = configuration.straight_cost;
-    let standard_cost = configuration.standard_cost;
-    let min_cost = corridor_cost.min(straight_cost).min(standard_cost);
-
-    // Initialize the structures.
-    open_set.clear();
-    // Expect the grids to be small enough so that half of usize::MAX is effectively infinity.
-    g_scores.clear();
-    g_scores.resize(tiles.len(), usize::MAX / 2);
-    parent.clear();
-    for i in 0..tiles.len() {
-        parent.push(i);
+/// Instead of resetting the scores and parents of the whole grid before every search, each tile
+/// remembers the search (generation) in which it was last written. Values from earlier searches
+/// are treated as unset, so a search costs only as much as the tiles it explores. The open set is
+/// an indexed heap, so a tile which is reached through a cheaper path has its key decreased
+/// instead of being inserted again.
+///
+/// The heuristic used is the manhatan distance (or the chebyshev distance for diagonal corridors)
+/// from this cell to the end multiplied by the minimum cost (with respect of all types of cost).
+/// The heuristic is admissible and consistent as we assume the best case of traveling only
+/// through the cheapest possible path. An optional cost field only adds to the costs, so it does
+/// not change these properties.
+pub struct Pathfinder {
+    open_set: IndexedHeap<usize>,
+    g_scores: Vec<usize>,
+    parent: Vec<usize>,
+    generations: Vec<u32>,
+    generation: u32,
+    path: Vec<usize>,
+    cost_field: Vec<usize>,
+}
+
+impl Pathfinder {
+    /// Creates a pathfinder for grids with the given number of tiles. Bigger grids are also
+    /// supported, but the buffers are reallocated.
+    pub fn new(tile_count: usize) -> Self {
+        Self {
+            open_set: IndexedHeap::with_capacity(tile_count),
+            g_scores: vec![0; tile_count],
+            parent: vec![0; tile_count],
+            generations: vec![0; tile_count],
+            generation: 0,
+            path: vec![],
+            cost_field: vec![],
+        }
     }
-    path.clear();
 
-    g_scores[start] = 0;
-    open_set.insert(manhattan(width, start, end) * min_cost, start);
+    /// Sets the extra cost of entering every tile of the grid, which is added to the cost of each
+    /// step. An empty field removes the extra costs. The field must have a value for every tile of
+    /// the grids which are searched.
+    pub fn set_cost_field(&mut self, cost_field: &[usize]) {
+        self.cost_field.clear();
+        self.cost_field.extend_from_slice(cost_field);
+    }
 
-    while let Some((f_cost, mut current)) = open_set.extract_min() {
-        let g_cost = f_cost - manhattan(width, current, end) * min_cost;
-        if g_cost > g_scores[current] {
-            continue;
+    /// The extra cost of moving a square of tiles of the given size to the anchor.
+    fn field_cost(&self, width: usize, anchor: usize, size: usize) -> usize {
+        if self.cost_field.is_empty() {
+            return 0;
         }
+        (0..size)
+            .flat_map(|row| (0..size).map(move |column| anchor + row * width + column))
+            .map(|tile| self.cost_field[tile])
+            .sum()
+    }
 
-        if current == end {
-            path.push(current);
-            while current != parent[current] {
-                current = parent[current];
-                path.push(current);
-            }
-            return;
+    /// The best known cost from the start to the tile in the current search. Expect the grids to
+    /// be small enough so that half of usize::MAX is effectively infinity.
+    fn g_score(&self, tile: usize) -> usize {
+        if self.generations[tile] == self.generation {
+            self.g_scores[tile]
+        } else {
+            usize::MAX / 2
         }
+    }
+
+    /// The tile from which the current search reached the tile. Tiles which have not been reached
+    /// are their own parents.
+    fn parent(&self, tile: usize) -> usize {
+        if self.generations[tile] == self.generation {
+            self.parent[tile]
+        } else {
+            tile
+        }
+    }
 
-        // We expect that the grid is such that it is guaranteed that the edges are unreachable.
-        for neighbor in [
-            current + width, // south
-            current - width, // north
-            current + 1,     // east
-            current - 1,     // west
-        ] {
-            // We cannot go through rooms or blockers.
-            if matches!(tiles[neighbor], Blocker | Room) {
-                continue;
+    fn set(&mut self, tile: usize, g_score: usize, parent: usize) {
+        self.generations[tile] = self.generation;
+        self.g_scores[tile] = g_score;
+        self.parent[tile] = parent;
+    }
+
+    /// Starts a new search, invalidating the values of all the previous ones.
+    fn next_generation(&mut self, tile_count: usize) {
+        if self.generations.len() < tile_count {
+            self.g_scores.resize(tile_count, 0);
+            self.parent.resize(tile_count, 0);
+            self.generations.resize(tile_count, 0);
+            self.open_set.reserve_handles(tile_count);
+        }
+        if self.generation == u32::MAX {
+            // Stamps of the old searches could be mistaken for new ones after wrapping around.
+            self.generations.fill(0);
+            self.generation = 0;
+        }
+        self.generation += 1;
+        self.open_set.clear();
+        self.path.clear();
+    }
+
+    /// Finds the cheapest corridor between the two tiles. Returns the path from the end to the
+    /// start, which is empty if there is no valid corridor.
+    pub fn find_path(
+        &mut self,
+        configuration: &Configuration,
+        start: usize,
+        end: usize,
+        width: usize,
+        tiles: &[Tile],
+    ) -> &[usize] {
+        self.find_wide_path(configuration, start, end, width, tiles, 1)
+    }
+
+    /// Finds the cheapest corridor of the given width between the two tiles. The path is made of
+    /// the north-west tiles of the squares the corridor is made of, so every tile of a square must
+    /// be clear of rooms and blockers. Corridors wider than one tile are made of 2x2 squares, so
+    /// the rules which disallow them are not followed.
+    pub fn find_wide_path(
+        &mut self,
+        configuration: &Configuration,
+        start: usize,
+        end: usize,
+        width: usize,
+        tiles: &[Tile],
+        corridor_width: usize,
+    ) -> &[usize] {
+        use Tile::*;
+
+        let disallow_corridor_squares =
+            configuration.disallow_corridor_squares && corridor_width == 1;
+        let corridor_cost = configuration.corridor_cost;
+        let straight_cost = configuration.straight_cost;
+        let standard_cost = configuration.standard_cost;
+        let diagonal_corridors = configuration.diagonal_corridors;
+        let diagonal_cost = configuration.diagonal_cost;
+        let mut min_cost = corridor_cost.min(straight_cost).min(standard_cost);
+        // With diagonal steps a single step can get closer in both directions at once, so the
+        // distance is measured in the number of steps needed when moving diagonally.
+        let distance = if diagonal_corridors {
+            min_cost = min_cost.min(diagonal_cost);
+            chebyshev
+        } else {
+            manhattan
+        };
+
+        self.next_generation(tiles.len());
+
+        self.set(start, 0, start);
+        self.open_set
+            .insert(distance(width, start, end) * min_cost, start);
+
+        while let Some((_, mut current)) = self.open_set.extract_min() {
+            if current == end {
+                self.path.push(current);
+                while current != self.parent(current) {
+                    current = self.parent(current);
+                    self.path.push(current);
+                }
+                return &self.path;
             }
-            if disallow_corridor_squares {
-                // We cannot go to another corridor neighbor if we also are a corridor neighbor. This
-                // covers one of the cases where the corridor would make a 2x2 square which is one of
-                // the rules the corridors must follow.
-                if matches!(tiles[current], CorridorNeighbor)
-                    && matches!(tiles[neighbor], CorridorNeighbor)
-                {
+
+            // We expect that the grid is such that it is guaranteed that the edges are
+            // unreachable. Diagonal neighbors are given with the two tiles at the corners of the
+            // step.
+            let neighbors = [
+                (current + width, None),                                     // south
+                (current - width, None),                                     // north
+                (current + 1, None),                                         // east
+                (current - 1, None),                                         // west
+                (current + width + 1, Some([current + width, current + 1])), // south-east
+                (current + width - 1, Some([current + width, current - 1])), // south-west
+                (current - width + 1, Some([current - width, current + 1])), // north-east
+                (current - width - 1, Some([current - width, current - 1])), // north-west
+            ];
+            let neighbor_count = if diagonal_corridors { 8 } else { 4 };
+            for &(neighbor, corners) in &neighbors[..neighbor_count] {
+                // We cannot go through rooms or blockers.
+                if !has_clearance(width, tiles, neighbor, corridor_width) {
                     continue;
                 }
-                // If the path makes a square, this is an invalid neighbor.
-                if make_square(
-                    width,
-                    [neighbor, current, parent[current], parent[parent[current]]],
-                ) {
-                    continue;
+                if let Some(corners) = corners {
+                    // A diagonal step cannot squeeze past the corner of a room or its doorway and
+                    // it cannot run alongside another corridor when squares are disallowed.
+                    if corners.iter().any(|&corner| {
+                        matches!(tiles[corner], Blocker | Room | Doorway)
+                            || (disallow_corridor_squares && matches!(tiles[corner], Corridor))
+                    }) {
+                        continue;
+                    }
+                }
+                let parent = self.parent(current);
+                if disallow_corridor_squares {
+                    // We cannot go to another corridor neighbor if we also are a corridor
+                    // neighbor. This covers one of the cases where the corridor would make a 2x2
+                    // square which is one of the rules the corridors must follow.
+                    if matches!(tiles[current], CorridorNeighbor)
+                        && matches!(tiles[neighbor], CorridorNeighbor)
+                    {
+                        continue;
+                    }
+                    // If the path makes a square, this is an invalid neighbor.
+                    if make_square(width, [neighbor, current, parent, self.parent(parent)]) {
+                        continue;
+                    }
                 }
-            }
 
-            let cost = if matches!(tiles[neighbor], Corridor) {
-                corridor_cost
-            } else if diff(current, parent[current]) == diff(neighbor, current) {
-                straight_cost
-            } else {
-                standard_cost
-            };
-
-            let tentative_g_score = g_scores[current] + cost;
-            if tentative_g_score < g_scores[neighbor] {
-                parent[neighbor] = current;
-                g_scores[neighbor] = tentative_g_score;
-                let f_score = tentative_g_score + manhattan(width, neighbor, end) * min_cost;
-                open_set.insert(f_score, neighbor);
+                let cost = if matches!(tiles[neighbor], Corridor) {
+                    corridor_cost
+                } else if corners.is_some() {
+                    diagonal_cost
+                } else if diff(current, parent) == diff(neighbor, current) {
+                    straight_cost
+                } else {
+                    standard_cost
+                };
+
+                let cost = cost + self.field_cost(width, neighbor, corridor_width);
+                let tentative_g_score = self.g_score(current) + cost;
+                if tentative_g_score < self.g_score(neighbor) {
+                    self.set(neighbor, tentative_g_score, current);
+                    let f_score = tentative_g_score + distance(width, neighbor, end) * min_cost;
+                    if self.open_set.contains(neighbor) {
+                        self.open_set.decrease_key(neighbor, f_score);
+                    } else {
+                        self.open_set.insert(f_score, neighbor);
+                    }
+                }
             }
         }
+
+        &self.path
     }
 }
 
@@ -148,15 +301,11 @@ mod test {
         )
     }
 
-    fn make_structures() -> (Heap<usize, usize>, Vec<usize>, Vec<usize>, Vec<usize>) {
-        (Heap::with_capacity(1000), vec![], vec![], vec![])
-    }
-
     #[test]
     #[should_panic]
     fn grid_edge_should_be_unreachable() {
         let configuration = Configuration::default();
-        let Grid { width, tiles } = Grid::from(
+        let Grid { width, tiles } = Grid::parse_lenient(
             "\
             #####\n\
             ##%##\n\
@@ -166,24 +315,14 @@ mod test {
         );
         let start = to_index(vec2u(1, 2), width);
         let end = to_index(vec2u(3, 2), width);
-        let (mut open_set, mut g_scores, mut parent, mut path) = make_structures();
-        a_star(
-            &configuration,
-            start,
-            end,
-            width,
-            &tiles,
-            &mut open_set,
-            &mut g_scores,
-            &mut parent,
-            &mut path,
-        );
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        pathfinder.find_path(&configuration, start, end, width, &tiles);
     }
 
     #[test]
     fn no_path() {
         let configuration = Configuration::default();
-        let Grid { width, tiles } = Grid::from(
+        let Grid { width, tiles } = Grid::parse_lenient(
             "\
             %%%%%\n\
             %#%#%\n\
@@ -195,25 +334,15 @@ mod test {
         let end = to_index(vec2u(3, 2), width);
         println!("{:?}", tiles[start]);
         println!("{:?}", tiles[start]);
-        let (mut open_set, mut g_scores, mut parent, mut path) = make_structures();
-        a_star(
-            &configuration,
-            start,
-            end,
-            width,
-            &tiles,
-            &mut open_set,
-            &mut g_scores,
-            &mut parent,
-            &mut path,
-        );
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
         assert_eq!(path.len(), 0, "A path was found when there was not one.");
     }
 
     #[test]
     fn straight() {
         let configuration = Configuration::default();
-        let Grid { width, tiles } = Grid::from(
+        let Grid { width, tiles } = Grid::parse_lenient(
             "\
             %%%%%%%%%%%\n\
             %#########%\n\
@@ -225,25 +354,15 @@ mod test {
         );
         let start = to_index(vec2u(1, 3), width);
         let end = to_index(vec2u(9, 3), width);
-        let (mut open_set, mut g_scores, mut parent, mut path) = make_structures();
-        a_star(
-            &configuration,
-            start,
-            end,
-            width,
-            &tiles,
-            &mut open_set,
-            &mut g_scores,
-            &mut parent,
-            &mut path,
-        );
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
         assert_eq!(path.len(), 9, "Path length was incorrect.");
     }
 
     #[test]
     fn with_wall() {
         let configuration = Configuration::default();
-        let Grid { width, tiles } = Grid::from(
+        let Grid { width, tiles } = Grid::parse_lenient(
             "\
             %%%%%%%%%%%\n\
             %#########%\n\
@@ -255,18 +374,8 @@ mod test {
         );
         let start = to_index(vec2u(1, 3), width);
         let end = to_index(vec2u(9, 3), width);
-        let (mut open_set, mut g_scores, mut parent, mut path) = make_structures();
-        a_star(
-            &configuration,
-            start,
-            end,
-            width,
-            &tiles,
-            &mut open_set,
-            &mut g_scores,
-            &mut parent,
-            &mut path,
-        );
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
         assert_eq!(path.len(), 13, "Path length was incorrect.");
     }
 
@@ -278,7 +387,7 @@ mod test {
             standard_cost: 10,
             ..Default::default()
         };
-        let Grid { width, tiles } = Grid::from(
+        let Grid { width, tiles } = Grid::parse_lenient(
             "\
             %%%%%%%%%%%\n\
             %#########%\n\
@@ -290,18 +399,146 @@ mod test {
         );
         let start = to_index(vec2u(1, 3), width);
         let end = to_index(vec2u(9, 3), width);
-        let (mut open_set, mut g_scores, mut parent, mut path) = make_structures();
-        a_star(
-            &configuration,
-            start,
-            end,
-            width,
-            &tiles,
-            &mut open_set,
-            &mut g_scores,
-            &mut parent,
-            &mut path,
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
+        assert_eq!(path.len(), 13, "Path length was incorrect.");
+    }
+
+    #[test]
+    fn reused_pathfinder() {
+        // The values from the first search should not leak into the second one.
+        let configuration = Configuration::default();
+        let Grid { width, tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %#########%\n\
+            %####%####%\n\
+            %d###%###d%\n\
+            %####%####%\n\
+            %####%####%\n\
+            %%%%%%%%%%%\n",
+        );
+        let start = to_index(vec2u(1, 3), width);
+        let end = to_index(vec2u(9, 3), width);
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        let first_path = pathfinder
+            .find_path(&configuration, start, end, width, &tiles)
+            .to_vec();
+        let second_path = pathfinder.find_path(&configuration, end, start, width, &tiles);
+        assert_eq!(second_path.len(), 13, "Path length was incorrect.");
+        assert_eq!(
+            first_path.len(),
+            second_path.len(),
+            "Both directions should have the same length."
         );
+
+        // Force the generation counter to wrap around.
+        pathfinder.generation = u32::MAX;
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
         assert_eq!(path.len(), 13, "Path length was incorrect.");
     }
+
+    #[test]
+    fn pathfinder_grows() {
+        let configuration = Configuration::default();
+        let Grid { width, tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %#########%\n\
+            %d#######d%\n\
+            %#########%\n\
+            %%%%%%%%%%%\n",
+        );
+        let start = to_index(vec2u(1, 2), width);
+        let end = to_index(vec2u(9, 2), width);
+        let mut pathfinder = Pathfinder::new(0);
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
+        assert_eq!(path.len(), 9, "Path length was incorrect.");
+    }
+
+    #[test]
+    fn diagonal_corridors() {
+        let configuration = Configuration {
+            diagonal_corridors: true,
+            ..Default::default()
+        };
+        let Grid { width, tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %d########%\n\
+            %#########%\n\
+            %#########%\n\
+            %#########%\n\
+            %########d%\n\
+            %%%%%%%%%%%\n",
+        );
+        let start = to_index(vec2u(1, 1), width);
+        let end = to_index(vec2u(9, 5), width);
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
+        assert_eq!(path.len(), 9, "Path length was incorrect.");
+    }
+
+    #[test]
+    fn diagonal_corridors_do_not_leak() {
+        // The blockers touch only by their corners, but the corridor cannot squeeze between them.
+        let configuration = Configuration {
+            diagonal_corridors: true,
+            ..Default::default()
+        };
+        let Grid { width, tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%%\n\
+            %d###%%\n\
+            %###%#%\n\
+            %##%##%\n\
+            %#%###%\n\
+            %%###d%\n\
+            %%%%%%%\n",
+        );
+        let start = to_index(vec2u(1, 1), width);
+        let end = to_index(vec2u(5, 5), width);
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
+        assert_eq!(path.len(), 0, "A path was found when there was not one.");
+    }
+
+    #[test]
+    fn cost_field() {
+        // The straight path is made expensive, so the corridor should go around it.
+        let configuration = Configuration::default();
+        let Grid { width, tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %#########%\n\
+            %#########%\n\
+            %d#######d%\n\
+            %#########%\n\
+            %#########%\n\
+            %%%%%%%%%%%\n",
+        );
+        let mut cost_field = vec![0; tiles.len()];
+        let expensive_tiles: Vec<usize> = (3..=7).map(|x| to_index(vec2u(x, 3), width)).collect();
+        for &tile in &expensive_tiles {
+            cost_field[tile] = 100;
+        }
+        let start = to_index(vec2u(1, 3), width);
+        let end = to_index(vec2u(9, 3), width);
+        let mut pathfinder = Pathfinder::new(tiles.len());
+        pathfinder.set_cost_field(&cost_field);
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
+        assert_eq!(path.len(), 11, "Path length was incorrect.");
+        assert!(
+            path.iter().all(|tile| !expensive_tiles.contains(tile)),
+            "The path should avoid the expensive tiles."
+        );
+
+        pathfinder.set_cost_field(&[]);
+        let path = pathfinder.find_path(&configuration, start, end, width, &tiles);
+        assert_eq!(
+            path.len(),
+            9,
+            "Without the field the path should be straight."
+        );
+    }
 }
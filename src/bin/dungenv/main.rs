@@ -3,6 +3,7 @@ mod ui;
 
 use dungen::Configuration;
 use dungen::grid::Grid;
+use dungen::prefab::PrefabLibrary;
 use dungen::room::{Doorway, Dungeon, Room};
 
 use thread::{Generator, Request, Result};
@@ -23,6 +24,7 @@ const CONTROLS: &str = "\
 
 const MAX_ROOM_COUNT: usize = 2_000;
 const MAX_MAP_DIMENSIONS: usize = 512;
+const PREFAB_PATH: &str = "prefabs";
 
 // Shorthands for raylib::math::Vector2
 #[cfg(not(tarpaulin_include))]
@@ -100,21 +102,22 @@ fn draw_graph(
 fn draw_grid(grid: &Grid, draw_handle: &mut impl RaylibDraw, highlight_special: bool) {
     use dungen::grid::Tile::*;
     draw_handle.clear_background(Color::BROWN);
-    for tile_index in 0..grid.tiles.len() {
-        let x = (tile_index % grid.width) as i32;
-        let y = (tile_index / grid.width) as i32;
-        if matches!(grid.tiles[tile_index], Room | Corridor | Doorway) {
-            draw_handle.draw_pixel(x, y, Color::YELLOW);
-        }
-        if highlight_special {
-            if matches!(grid.tiles[tile_index], Doorway) {
-                draw_handle.draw_pixel(x, y, Color::BLUEVIOLET);
-            }
-            if matches!(grid.tiles[tile_index], Blocker) {
-                draw_handle.draw_pixel(x, y, Color::PURPLE);
+    for (y, row) in grid.rows().enumerate() {
+        for (x, &tile) in row.iter().enumerate() {
+            let (x, y) = (x as i32, y as i32);
+            if matches!(tile, Room | Corridor | Doorway) {
+                draw_handle.draw_pixel(x, y, Color::YELLOW);
             }
-            if matches!(grid.tiles[tile_index], CorridorNeighbor) {
-                draw_handle.draw_pixel(x, y, Color::YELLOWGREEN);
+            if highlight_special {
+                if matches!(tile, Doorway) {
+                    draw_handle.draw_pixel(x, y, Color::BLUEVIOLET);
+                }
+                if matches!(tile, Blocker) {
+                    draw_handle.draw_pixel(x, y, Color::PURPLE);
+                }
+                if matches!(tile, CorridorNeighbor) {
+                    draw_handle.draw_pixel(x, y, Color::YELLOWGREEN);
+                }
             }
         }
     }
@@ -137,6 +140,13 @@ fn main() {
     let mut target_room_count: usize = 30;
     let mut reintroduced_corridor_density: f32 = 0.5;
     let mut configuration = Configuration::default();
+    // The prefab library is loaded from the prefabs directory of the working directory.
+    if std::path::Path::new(PREFAB_PATH).exists() {
+        match PrefabLibrary::load(PREFAB_PATH) {
+            Ok(library) => configuration.prefabs.library = library,
+            Err(error) => eprintln!("{error}"),
+        }
+    }
     // ============================== Configuration variables
 
     // ============================== State variables
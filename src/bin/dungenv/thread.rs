@@ -1,6 +1,10 @@
 use dungen::Configuration;
+use dungen::cave;
 use dungen::grid::Grid;
+use dungen::interior;
 use dungen::maze;
+use dungen::maze_fill::fill_with_mazes;
+use dungen::prefab;
 use dungen::room::{Dungeon, Edges};
 use dungen::vec;
 
@@ -43,7 +47,8 @@ pub struct Generator {
 #[cfg(not(tarpaulin_include))]
 pub fn make_generator() -> Generator {
     use dungen::{
-        grid::make_grid, mst::pick_corridors, room::generate_rooms, triangulation::triangulate,
+        drunkard::make_cave_grid, grid::make_grid, mst::pick_corridors, room::generate_rooms,
+        triangulation::triangulate,
     };
 
     let (requests, request_receiver) = mpsc::channel::<Request>();
@@ -51,6 +56,9 @@ pub fn make_generator() -> Generator {
 
     let handle = std::thread::spawn(move || {
         let mut rng = rand::rng();
+        // The prefab rooms of the last new dungeon, which is reused when only the corridors are
+        // generated again.
+        let mut prefab_rooms = vec![];
         #[allow(clippy::while_let_loop)]
         loop {
             let request = match request_receiver.recv() {
@@ -70,11 +78,38 @@ pub fn make_generator() -> Generator {
                         Some(target_room_count),
                         &mut rng,
                     );
+                    prefab_rooms =
+                        prefab::make_prefab_rooms(&mut rng, &configuration, &mut dungeon);
                     let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
                     let corridors =
                         pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
-                    let mut grid = make_grid(&configuration, grid_dimensions, &dungeon, &corridors);
-                    maze::make_mazes(&mut rng, &configuration, &mut grid, &dungeon);
+                    let result = match configuration.drunkard_walk {
+                        Some(_) => make_cave_grid(
+                            &configuration,
+                            grid_dimensions,
+                            &dungeon,
+                            &corridors,
+                            &mut rng,
+                        ),
+                        None => make_grid(&configuration, grid_dimensions, &dungeon, &corridors),
+                    };
+                    let mut grid = match result {
+                        Ok((grid, _)) => grid,
+                        // Display the incomplete grid, the missing corridors are visible.
+                        Err(error) => error.grid,
+                    };
+                    // The new doorways are added to a copy of the dungeon, since the dungeon is
+                    // reused when only the corridors are generated again.
+                    let mut filled = dungeon.clone();
+                    if let Some(maze_fill) = &configuration.maze_fill {
+                        let doorways = fill_with_mazes(maze_fill, &mut grid, &dungeon, &mut rng);
+                        filled.doorways.extend(doorways);
+                    }
+                    prefab::stamp_prefab_rooms(&configuration, &prefab_rooms, &mut grid, &filled);
+                    prefab::stamp_prefabs(&mut rng, &configuration, &mut grid, &filled);
+                    cave::make_caves(&mut rng, &configuration, &mut grid, &filled);
+                    interior::make_interiors(&mut rng, &configuration, &mut grid, &filled);
+                    maze::make_mazes(&mut rng, &configuration, &mut grid, &filled);
                     if results_sender
                         .send(Result::New {
                             dungeon,
@@ -90,12 +125,35 @@ pub fn make_generator() -> Generator {
                 Request::CorridorsAndMazes {
                     configuration,
                     grid_dimensions,
-                    dungeon: rooms,
+                    dungeon: mut rooms,
                     mut triangulation,
                 } => {
                     let corridors =
                         pick_corridors(&configuration, &rooms, &mut triangulation, &mut rng);
-                    let mut grid = make_grid(&configuration, grid_dimensions, &rooms, &corridors);
+                    let result = match configuration.drunkard_walk {
+                        Some(_) => make_cave_grid(
+                            &configuration,
+                            grid_dimensions,
+                            &rooms,
+                            &corridors,
+                            &mut rng,
+                        ),
+                        None => make_grid(&configuration, grid_dimensions, &rooms, &corridors),
+                    };
+                    let mut grid = match result {
+                        Ok((grid, _)) => grid,
+                        Err(error) => error.grid,
+                    };
+                    // The dungeon is a copy sent with the request, so the new doorways can be
+                    // added to it directly.
+                    if let Some(maze_fill) = &configuration.maze_fill {
+                        let doorways = fill_with_mazes(maze_fill, &mut grid, &rooms, &mut rng);
+                        rooms.doorways.extend(doorways);
+                    }
+                    prefab::stamp_prefab_rooms(&configuration, &prefab_rooms, &mut grid, &rooms);
+                    prefab::stamp_prefabs(&mut rng, &configuration, &mut grid, &rooms);
+                    cave::make_caves(&mut rng, &configuration, &mut grid, &rooms);
+                    interior::make_interiors(&mut rng, &configuration, &mut grid, &rooms);
                     maze::make_mazes(&mut rng, &configuration, &mut grid, &rooms);
                     if results_sender
                         .send(Result::Corridors { corridors, grid })
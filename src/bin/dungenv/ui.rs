@@ -1,7 +1,13 @@
 use crate::{CONTROLS, MAX_MAP_DIMENSIONS, MAX_ROOM_COUNT};
 use crate::{Generator, Request};
 use dungen::Configuration;
+use dungen::cave::CellularCave;
+use dungen::drunkard::DrunkardWalk;
 use dungen::grid::Grid;
+use dungen::interior::{InteriorFeature, RoomInteriors};
+use dungen::maze::MazeAlgorithm;
+use dungen::maze_fill::MazeFill;
+use dungen::mst::{EdgeMetric, LoopPreference};
 use dungen::room::{Dungeon, Edges};
 use dungen::vec;
 
@@ -59,7 +65,8 @@ pub fn draw_ui(
         .resizable(false)
         .movable(false)
         .build(|| {
-            { // ============================== min_room_dimension
+            {
+                // ============================== min_room_dimension
                 ui.slider(
                     "Min Room Dimensions",
                     5,
@@ -73,8 +80,8 @@ pub fn draw_ui(
                 }
             } // ============================== min_room_dimension
 
-
-            { // ============================== max_room_dimension
+            {
+                // ============================== max_room_dimension
                 configuration.max_room_dimension = configuration
                     .max_room_dimension
                     .max(configuration.min_room_dimension);
@@ -86,28 +93,28 @@ pub fn draw_ui(
                 );
                 if ui.is_item_hovered() {
                     ui.tooltip_text(
-                        "Maximum tile length of a room. Must be greater than or equal to the minimum."
+                        "Maximum tile length of a room. Must be greater than or equal to the \
+                         minimum.",
                     );
                 }
             } // ============================== max_room_dimension
 
-
-            { // ============================== min_padding
+            {
+                // ============================== min_padding
                 ui.slider("Min padding", 3, 20, &mut configuration.min_padding);
                 if ui.is_item_hovered() {
                     ui.tooltip_text(
-                        "The minimum distance between rooms and the map border to guarantee that doorways \
-                         are accessible.");
+                        "The minimum distance between rooms and the map border to guarantee that \
+                         doorways are accessible.",
+                    );
                 }
             } // ============================== min_padding
 
-
-            { // ============================== doorway_offset
+            {
+                // ============================== doorway_offset
                 let max_doorway_offset = (configuration.min_room_dimension >> 1)
                     - ((!configuration.min_room_dimension) & 1);
-                configuration.doorway_offset = configuration
-                    .doorway_offset
-                    .min(max_doorway_offset);
+                configuration.doorway_offset = configuration.doorway_offset.min(max_doorway_offset);
                 ui.slider(
                     "Doorway offset",
                     1,
@@ -119,39 +126,287 @@ pub fn draw_ui(
                 }
             } // ============================== doorway_offset
 
-
-            { // ============================== max_fail_count
+            {
+                // ============================== max_fail_count
                 ui.slider("Max Fail Count", 1, 200, &mut configuration.max_fail_count);
-                if ui.slider(
-                    "Corridor Density",
-                    0.0,
-                    1.0,
-                    reintroduced_corridor_density,
-                ) {
+                if ui.slider("Corridor Density", 0.0, 1.0, reintroduced_corridor_density) {
                     configuration.reintroduced_corridor_density =
                         ((*reintroduced_corridor_density * 1000.0) as usize, 1000);
                 }
                 if ui.is_item_hovered() {
                     ui.tooltip_text(
                         "What percentage of edges from the triangulation on average \
-                         should be reintroduced as corridors.");
+                         should be reintroduced as corridors.",
+                    );
                 }
             } // ============================== max_fail_count
 
+            {
+                // ============================== reintroduced_corridor_count
+                let mut fixed_loop_count = configuration.reintroduced_corridor_count.is_some();
+                ui.checkbox("Fixed Loop Count", &mut fixed_loop_count);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Reintroduce an exact number of edges from the triangulation \
+                         as corridors instead of a percentage of them.",
+                    );
+                }
+                if fixed_loop_count {
+                    let (mut min_loops, mut max_loops) =
+                        configuration.reintroduced_corridor_count.unwrap_or((0, 0));
+                    ui.slider("Min Loops", 0, 200, &mut min_loops);
+                    max_loops = max_loops.max(min_loops);
+                    ui.slider("Max Loops", min_loops, 200, &mut max_loops);
+                    configuration.reintroduced_corridor_count = Some((min_loops, max_loops));
+
+                    use LoopPreference::*;
+                    ui.radio_button("Random Loops", &mut configuration.loop_preference, Random);
+                    ui.same_line();
+                    ui.radio_button("Short Loops", &mut configuration.loop_preference, Short);
+                    ui.same_line();
+                    ui.radio_button("Long Loops", &mut configuration.loop_preference, Long);
+                } else {
+                    configuration.reintroduced_corridor_count = None;
+                }
+            } // ============================== reintroduced_corridor_count
+
+            {
+                // ============================== edge_metric
+                let mut metric_index = match configuration.edge_metric {
+                    EdgeMetric::Euclidean => 0,
+                    EdgeMetric::Manhattan => 1,
+                    EdgeMetric::CarveCost => 2,
+                    EdgeMetric::Custom(_) => 0,
+                };
+                if ui.combo_simple_string(
+                    "Edge Metric",
+                    &mut metric_index,
+                    &["Euclidean", "Manhattan", "Carve Cost"],
+                ) {
+                    configuration.edge_metric = match metric_index {
+                        1 => EdgeMetric::Manhattan,
+                        2 => EdgeMetric::CarveCost,
+                        _ => EdgeMetric::Euclidean,
+                    };
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "How the edges of the triangulation are weighted when \
+                         picking the minimum spanning tree.",
+                    );
+                }
+                ui.slider(
+                    "Edge Weight Jitter",
+                    0,
+                    200,
+                    &mut configuration.edge_weight_jitter,
+                );
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Each edge weight is increased by a random percentage \
+                         between 0 and this value.",
+                    );
+                }
+            } // ============================== edge_metric
+
+            {
+                // ============================== room_degree
+                ui.slider("Min Room Degree", 0, 10, &mut configuration.room_degree.0);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "The minimum number of corridors connected to a room, if the \
+                         triangulation has enough edges.",
+                    );
+                }
+                let mut limit_room_degree = configuration.room_degree.1 != usize::MAX;
+                ui.checkbox("Limit Room Degree", &mut limit_room_degree);
+                if limit_room_degree {
+                    let min_max_degree = configuration.room_degree.0.max(1);
+                    configuration.room_degree.1 =
+                        configuration.room_degree.1.clamp(min_max_degree, 10);
+                    ui.slider(
+                        "Max Room Degree",
+                        min_max_degree,
+                        10,
+                        &mut configuration.room_degree.1,
+                    );
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "The maximum number of corridors connected to a room. It is \
+                             exceeded only to keep all rooms connected.",
+                        );
+                    }
+                } else {
+                    configuration.room_degree.1 = usize::MAX;
+                }
+            } // ============================== room_degree
+
+            ui.spacing();
+
+            {
+                // ============================== corridor_width
+                let max_width = configuration.min_room_dimension;
+                configuration.corridor_width = configuration.corridor_width.min(max_width);
+                ui.slider(
+                    "Corridor Width",
+                    1,
+                    max_width,
+                    &mut configuration.corridor_width,
+                );
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Width of the corridors in tiles. Cannot be greater than the \
+                         minimum room dimension.",
+                    );
+                }
+                let mut separate_width = configuration.reintroduced_corridor_width.is_some();
+                ui.checkbox("Separate Loop Corridor Width", &mut separate_width);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Whether the corridors which are not needed to connect the rooms \
+                         have their own width.",
+                    );
+                }
+                if separate_width {
+                    let width = configuration
+                        .reintroduced_corridor_width
+                        .get_or_insert(configuration.corridor_width);
+                    *width = (*width).min(max_width);
+                    ui.slider("Loop Corridor Width", 1, max_width, width);
+                } else {
+                    configuration.reintroduced_corridor_width = None;
+                }
+            } // ============================== corridor_width
+
             ui.spacing();
 
-            { // ============================== corridor costs
-                ui.checkbox("Disallow Corridor Squares", &mut configuration.disallow_corridor_squares);
+            {
+                // ============================== corridor shape limits
+                let mut limit_turns = configuration.max_corridor_turns.is_some();
+                ui.checkbox("Limit Corridor Turns", &mut limit_turns);
                 if ui.is_item_hovered() {
                     ui.tooltip_text(
-                        "Whether the corridors can make a 2x2 square.");
+                        "Corridors with more turns are rerouted or, if the rooms are \
+                         connected through other corridors, dropped.",
+                    );
+                }
+                if limit_turns {
+                    let max_turns = configuration.max_corridor_turns.get_or_insert(4);
+                    ui.slider("Max Corridor Turns", 0, 20, max_turns);
+                } else {
+                    configuration.max_corridor_turns = None;
+                }
+
+                let mut limit_stretch = configuration.max_corridor_stretch.is_some();
+                ui.checkbox("Limit Corridor Length", &mut limit_stretch);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Corridors longer than the given percentage of the Manhattan \
+                         distance between their doorways are rerouted or dropped.",
+                    );
+                }
+                if limit_stretch {
+                    let max_stretch = configuration.max_corridor_stretch.get_or_insert(200);
+                    ui.slider("Max Corridor Length %", 100, 500, max_stretch);
+                } else {
+                    configuration.max_corridor_stretch = None;
+                }
+            } // ============================== corridor shape limits
+
+            ui.spacing();
+
+            {
+                // ============================== drunkard_walk
+                let mut cave_corridors = configuration.drunkard_walk.is_some();
+                ui.checkbox("Cave Corridors", &mut cave_corridors);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Carve the corridors with biased random walks instead of the A* \
+                         algorithm, which gives irregular tunnels.",
+                    );
+                }
+                if cave_corridors {
+                    let drunkard_walk = configuration
+                        .drunkard_walk
+                        .get_or_insert_with(DrunkardWalk::default);
+                    ui.slider("Walk Bias %", 0, 100, &mut drunkard_walk.bias);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "Percentage of the steps which go towards the other doorway.",
+                        );
+                    }
+                    ui.slider("Walk Patience", 1, 20, &mut drunkard_walk.patience);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "Steps of the walk for every tile of distance between the doorways, \
+                             after which the corridor is finished with the A* algorithm.",
+                        );
+                    }
+                } else {
+                    configuration.drunkard_walk = None;
+                }
+            } // ============================== drunkard_walk
+
+            ui.spacing();
+
+            {
+                // ============================== maze_fill
+                let mut fill_with_mazes = configuration.maze_fill.is_some();
+                ui.checkbox("Fill With Mazes", &mut fill_with_mazes);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Grow maze passages in the empty space between the rooms and the \
+                         corridors and connect them to the rooms with new doorways.",
+                    );
+                }
+                if fill_with_mazes {
+                    let maze_fill = configuration
+                        .maze_fill
+                        .get_or_insert_with(MazeFill::default);
+                    ui.slider(
+                        "Passage Straightness %",
+                        0,
+                        100,
+                        &mut maze_fill.straightness,
+                    );
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "Percentage of the steps of the passages which continue in the \
+                             same direction.",
+                        );
+                    }
+                    ui.slider(
+                        "Dead End Pruning",
+                        0.0,
+                        1.0,
+                        &mut maze_fill.dead_end_pruning,
+                    );
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("What proportion of the dead ends is removed.");
+                    }
+                } else {
+                    configuration.maze_fill = None;
+                }
+            } // ============================== maze_fill
+
+            ui.spacing();
+
+            {
+                // ============================== corridor costs
+                ui.checkbox(
+                    "Disallow Corridor Squares",
+                    &mut configuration.disallow_corridor_squares,
+                );
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Whether the corridors can make a 2x2 square.");
                 }
                 ui.slider("Corridor Cost", 1, 40, &mut configuration.corridor_cost);
                 if ui.is_item_hovered() {
                     ui.tooltip_text(
                         "Cost for the A* algorithm when we go through an already \
                          placed corridor. The relationship between this value and \
-                         the other two costs determines the shape of the corridors.");
+                         the other two costs determines the shape of the corridors.",
+                    );
                 }
                 ui.slider("Straight Cost", 1, 40, &mut configuration.straight_cost);
                 if ui.is_item_hovered() {
@@ -159,19 +414,35 @@ pub fn draw_ui(
                         "Cost for the A* algorithm when we go to a tile which is in the same \
                          direction (horizontal or vertical) from which we came to the current \
                          tile. When lower than the standard cost makes the corridors straight \
-                         hence the name.");
+                         hence the name.",
+                    );
                 }
                 ui.slider("Standard Cost", 1, 40, &mut configuration.standard_cost);
                 if ui.is_item_hovered() {
                     ui.tooltip_text(
                         "Default cost for the A* algorithm. The corridors can move \
-                         only horizontally or vertically.");
+                         only horizontally or vertically unless diagonal corridors \
+                         are enabled.",
+                    );
+                }
+                ui.checkbox("Diagonal Corridors", &mut configuration.diagonal_corridors);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Whether the corridors can also move diagonally.");
+                }
+                if configuration.diagonal_corridors {
+                    ui.slider("Diagonal Cost", 1, 40, &mut configuration.diagonal_cost);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "Cost for the A* algorithm when the corridor moves diagonally.",
+                        );
+                    }
                 }
             } // ============================== corridor costs
 
             ui.spacing();
 
-            { // ============================== grid dimensions
+            {
+                // ============================== grid dimensions
                 *dimensions_changed |= ui.slider(
                     "Grid Width",
                     configuration.min_room_dimension + configuration.min_padding * 2,
@@ -188,47 +459,220 @@ pub fn draw_ui(
 
             let max_room_count = (*grid_height * *grid_width).min(MAX_ROOM_COUNT);
             *target_room_count = (*target_room_count).min(max_room_count);
-            ui.slider(
-                "Target Room Count",
-                1,
-                max_room_count,
-                target_room_count,
-            );
+            ui.slider("Target Room Count", 1, max_room_count, target_room_count);
 
             ui.spacing();
 
-            { // ============================== maze options
-                configuration.min_maze_dimension = configuration.min_maze_dimension
+            {
+                // ============================== maze options
+                configuration.min_maze_dimension = configuration
+                    .min_maze_dimension
                     .max(configuration.min_room_dimension)
                     .min(configuration.max_room_dimension);
-                configuration.max_maze_dimension = configuration.max_maze_dimension
+                configuration.max_maze_dimension = configuration
+                    .max_maze_dimension
                     .max(configuration.min_maze_dimension)
                     .min(configuration.max_room_dimension);
                 ui.slider(
                     "Min Maze Dimensions",
                     configuration.min_room_dimension,
                     configuration.max_room_dimension,
-                    &mut configuration.min_maze_dimension
+                    &mut configuration.min_maze_dimension,
                 );
                 ui.slider(
                     "Max Maze Dimensions",
                     configuration.min_maze_dimension,
                     configuration.max_room_dimension,
-                    &mut configuration.max_maze_dimension
-                );
-                ui.slider(
-                    "Maze Chance",
-                    0.0,
-                    1.0,
-                    &mut configuration.maze_chance,
+                    &mut configuration.max_maze_dimension,
                 );
+                ui.slider("Maze Chance", 0.0, 1.0, &mut configuration.maze_chance);
                 if ui.is_item_hovered() {
                     ui.tooltip_text(
                         "What percentage of rooms with the given minimum dimesions \
-                         should have mazes generated in them.");
+                         should have mazes generated in them.",
+                    );
+                }
+                let mut algorithm_index = MazeAlgorithm::ALL
+                    .iter()
+                    .position(|&algorithm| algorithm == configuration.maze_algorithm)
+                    .unwrap_or(0);
+                if ui.combo_simple_string(
+                    "Maze Algorithm",
+                    &mut algorithm_index,
+                    &[
+                        "Kruskal",
+                        "Recursive Backtracker",
+                        "Prim",
+                        "Wilson",
+                        "Eller",
+                        "Binary Tree",
+                    ],
+                ) {
+                    configuration.maze_algorithm = MazeAlgorithm::ALL[algorithm_index];
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("The algorithm which carves the passages of the mazes.");
+                }
+                ui.slider("Maze Braid", 0.0, 1.0, &mut configuration.maze_braid);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("What proportion of the dead ends of the mazes is removed.");
+                }
+                ui.slider("Maze Bias", -1.0, 1.0, &mut configuration.maze_bias);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Favors horizontal (positive) or vertical (negative) \
+                         passages in the mazes.",
+                    );
                 }
             } // ============================== maze options
 
+            ui.spacing();
+
+            {
+                // ============================== cellular_cave
+                let mut cave_rooms = configuration.cellular_cave.is_some();
+                ui.checkbox("Cave Rooms", &mut cave_rooms);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Turn some of the rooms into caves with a cellular automaton. \
+                         Mazes are not placed in caves.",
+                    );
+                }
+                if cave_rooms {
+                    let cave = configuration
+                        .cellular_cave
+                        .get_or_insert_with(CellularCave::default);
+                    ui.slider("Cave Chance", 0.0, 1.0, &mut cave.chance);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("What proportion of the rooms become caves.");
+                    }
+                    ui.slider("Cave Fill %", 0, 100, &mut cave.fill_percent);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Percentage of the tiles which start as walls.");
+                    }
+                    ui.slider("Cave Birth", 0, 9, &mut cave.birth);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "How many of the eight neighbors of an open tile must be walls \
+                             for it to become a wall.",
+                        );
+                    }
+                    ui.slider("Cave Survival", 0, 9, &mut cave.survival);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "How many of the eight neighbors of a wall must be walls for it \
+                             to stay a wall.",
+                        );
+                    }
+                    ui.slider("Cave Iterations", 0, 10, &mut cave.iterations);
+                } else {
+                    configuration.cellular_cave = None;
+                }
+            } // ============================== cellular_cave
+
+            ui.spacing();
+
+            {
+                // ============================== room_interiors
+                let mut decorate_rooms = configuration.room_interiors.is_some();
+                ui.checkbox("Room Interiors", &mut decorate_rooms);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Decorate some of the rooms with walls inside them. \
+                         Mazes are not placed in decorated rooms.",
+                    );
+                }
+                if decorate_rooms {
+                    let interiors = configuration
+                        .room_interiors
+                        .get_or_insert_with(RoomInteriors::default);
+                    ui.slider("Interior Chance", 0.0, 1.0, &mut interiors.chance);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "What proportion of the rooms with the given dimensions \
+                             get an interior.",
+                        );
+                    }
+                    interiors.min_dimension = interiors
+                        .min_dimension
+                        .max(configuration.min_room_dimension)
+                        .min(configuration.max_room_dimension);
+                    interiors.max_dimension = interiors
+                        .max_dimension
+                        .max(interiors.min_dimension)
+                        .min(configuration.max_room_dimension);
+                    ui.slider(
+                        "Min Interior Dimensions",
+                        configuration.min_room_dimension,
+                        configuration.max_room_dimension,
+                        &mut interiors.min_dimension,
+                    );
+                    ui.slider(
+                        "Max Interior Dimensions",
+                        interiors.min_dimension,
+                        configuration.max_room_dimension,
+                        &mut interiors.max_dimension,
+                    );
+                    for (feature, label) in InteriorFeature::ALL.into_iter().zip([
+                        "Pillars",
+                        "Inner Ring",
+                        "Split",
+                        "Nested Room",
+                    ]) {
+                        let mut enabled = interiors.features.contains(&feature);
+                        if ui.checkbox(label, &mut enabled) {
+                            if enabled {
+                                interiors.features.push(feature);
+                            } else {
+                                interiors.features.retain(|&other| other != feature);
+                            }
+                        }
+                    }
+                } else {
+                    configuration.room_interiors = None;
+                }
+            } // ============================== room_interiors
+
+            ui.spacing();
+
+            {
+                // ============================== prefabs
+                let prefab_count = configuration.prefabs.library.prefabs.len();
+                if prefab_count > 0 {
+                    ui.slider(
+                        "Prefab Room Chance",
+                        0.0,
+                        1.0,
+                        &mut configuration.prefabs.room_chance,
+                    );
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(format!(
+                            "What proportion of the rooms are replaced by one of the \
+                             {prefab_count} prefabs."
+                        ));
+                    }
+                    ui.slider(
+                        "Prefab Stamp Chance",
+                        0.0,
+                        1.0,
+                        &mut configuration.prefabs.stamp_chance,
+                    );
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "What proportion of the other rooms get a prefab \
+                             stamped inside them.",
+                        );
+                    }
+                } else {
+                    ui.text("No prefabs loaded.");
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(
+                            "Prefabs are loaded from the prefabs directory at startup.",
+                        );
+                    }
+                }
+            } // ============================== prefabs
+
             ui.label_text("Controls", CONTROLS);
 
             let token = ui.begin_disabled(*generating);
@@ -246,7 +690,8 @@ pub fn draw_ui(
             }
             ui.columns(1, "", false);
 
-            if let Some(_token) = ui.modal_popup_config("Export Message")
+            if let Some(_token) = ui
+                .modal_popup_config("Export Message")
                 .save_settings(false)
                 .always_auto_resize(true)
                 .resizable(false)
@@ -267,11 +712,15 @@ pub fn draw_ui(
             ui.columns(2, "Generation", false);
             if ui.button("Regenerate") {
                 *generating = true;
-                if generator.requests.send(Request::New {
-                    configuration: configuration.clone(),
-                    grid_dimensions: vec::vec2u(*grid_width, *grid_height),
-                    target_room_count: *target_room_count,
-                }).is_err() {
+                if generator
+                    .requests
+                    .send(Request::New {
+                        configuration: configuration.clone(),
+                        grid_dimensions: vec::vec2u(*grid_width, *grid_height),
+                        target_room_count: *target_room_count,
+                    })
+                    .is_err()
+                {
                     return;
                 };
             }
@@ -279,12 +728,16 @@ pub fn draw_ui(
             ui.next_column();
             if ui.button("Regenerate Extras") {
                 *generating = true;
-                if generator.requests.send(Request::CorridorsAndMazes {
-                    configuration: configuration.clone(),
-                    grid_dimensions: vec::vec2u(*grid_width, *grid_height),
-                    dungeon: dungeon.clone(),
-                    triangulation: triangulation.clone()
-                }).is_err() {
+                if generator
+                    .requests
+                    .send(Request::CorridorsAndMazes {
+                        configuration: configuration.clone(),
+                        grid_dimensions: vec::vec2u(*grid_width, *grid_height),
+                        dungeon: dungeon.clone(),
+                        triangulation: triangulation.clone(),
+                    })
+                    .is_err()
+                {
                     return;
                 };
             }
@@ -1,117 +1,368 @@
-pub struct Heap<K, T> {
-    keys: Vec<K>,
-    aux: Vec<T>,
-    len: usize,
+/// Decides the order in which the elements leave a heap.
+pub trait Comparator<K> {
+    /// Whether the key `a` should leave the heap before the key `b`.
+    fn precedes(&self, a: &K, b: &K) -> bool;
+}
+
+/// Smallest keys first, the default order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MinFirst;
+
+impl<K: Ord> Comparator<K> for MinFirst {
+    fn precedes(&self, a: &K, b: &K) -> bool {
+        a < b
+    }
+}
+
+/// Biggest keys first, which turns the heap into a max-heap.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaxFirst;
+
+impl<K: Ord> Comparator<K> for MaxFirst {
+    fn precedes(&self, a: &K, b: &K) -> bool {
+        a > b
+    }
+}
+
+/// Any closure which tells whether its first argument precedes the second one.
+impl<K, F> Comparator<K> for F
+where
+    F: Fn(&K, &K) -> bool,
+{
+    fn precedes(&self, a: &K, b: &K) -> bool {
+        self(a, b)
+    }
+}
+
+/// A d-ary heap of keys with an additional value (payload) each. By default it is a binary
+/// min-heap, but the order can be changed with a comparator and the number of children of every
+/// node with the arity. Higher arities make the heap shallower, which makes insertions cheaper and
+/// extractions more expensive.
+pub struct Heap<K, T, C = MinFirst> {
+    entries: Vec<(K, T)>,
+    comparator: C,
+    arity: usize,
 }
 
 impl<K, T> Heap<K, T>
 where
-    K: Default + Copy + Ord + Eq,
-    T: Default + Copy,
+    K: Ord,
 {
-    /// Inserts a dummy element, because the calculations of the indices of a 1-based binary heap
-    /// are easier. Since the number of elements in the graph is usually known, a facility to
-    /// preallocate the needed number of slots in the arrays is provided.
+    /// Since the number of elements in the graph is usually known, a facility to preallocate the
+    /// needed number of slots in the arrays is provided.
     pub fn with_capacity(capacity: usize) -> Self {
-        let mut keys = Vec::with_capacity(capacity + 1);
-        let mut aux = Vec::with_capacity(capacity + 1);
+        Self::with_comparator(MinFirst, capacity)
+    }
+}
+
+impl<K, T, C> Heap<K, T, C>
+where
+    C: Comparator<K>,
+{
+    /// Creates a binary heap which orders its keys with the comparator.
+    pub fn with_comparator(comparator: C, capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            comparator,
+            arity: 2,
+        }
+    }
 
-        keys.push(K::default());
-        aux.push(T::default());
+    /// Creates a heap from the elements in linear time by sifting down every node which has
+    /// children, starting from the last one (bottom-up construction).
+    pub fn from_vec_with_comparator(entries: Vec<(K, T)>, comparator: C) -> Self {
+        let mut heap = Self {
+            entries,
+            comparator,
+            arity: 2,
+        };
+        heap.heapify();
+        heap
+    }
 
-        Self { keys, aux, len: 0 }
+    /// Changes the number of children of every node. The elements which are already in the heap
+    /// are reordered.
+    pub fn with_arity(mut self, arity: usize) -> Self {
+        assert!(arity >= 2, "The arity of a heap must be at least 2.");
+        self.arity = arity;
+        self.heapify();
+        self
     }
 
-    /// Returns the minimum element if it exists without removing it from the heap.
-    pub fn min(&self) -> Option<(K, T)> {
-        if self.len == 0 {
-            None
-        } else {
-            Some((self.keys[1], self.aux[1]))
+    fn heapify(&mut self) {
+        if self.entries.len() < 2 {
+            return;
+        }
+        let last_parent = (self.entries.len() - 2) / self.arity;
+        for index in (0..=last_parent).rev() {
+            self.descend(index, self.entries.len());
         }
     }
 
+    fn precedes(&self, a: usize, b: usize) -> bool {
+        self.comparator
+            .precedes(&self.entries[a].0, &self.entries[b].0)
+    }
+
     /// Utility procedure to update the heap after inserting an element.
     fn ascend(&mut self, mut index: usize) {
-        let mut parent = index >> 1;
-        while parent > 0 {
-            if self.keys[index] < self.keys[parent] {
-                self.swap(index, parent);
-            } else {
+        while index > 0 {
+            let parent = (index - 1) / self.arity;
+            if !self.precedes(index, parent) {
                 break;
             }
-
+            self.entries.swap(index, parent);
             index = parent;
-            parent >>= 1;
         }
     }
 
-    /// Utility procedure to update the heap after removing an element.
-    fn descend(&mut self, mut index: usize) {
-        let mut left_child = index << 1;
-        let mut right_child;
-        let mut min_child;
-
-        while left_child < self.keys.len() {
-            right_child = left_child + 1;
-
-            min_child = if right_child < self.keys.len()
-                && self.keys[left_child] > self.keys[right_child]
-            {
-                right_child
-            } else {
-                left_child
-            };
-
-            if self.keys[index] <= self.keys[min_child] {
+    /// Utility procedure to update the heap after removing an element. Only the first `len`
+    /// elements are considered part of the heap.
+    fn descend(&mut self, mut index: usize, len: usize) {
+        loop {
+            let first_child = index * self.arity + 1;
+            if first_child >= len {
                 break;
             }
-
-            self.swap(index, min_child);
-            index = min_child;
-            left_child = index << 1;
+            let last_child = (first_child + self.arity).min(len);
+            let mut best_child = first_child;
+            for child in first_child + 1..last_child {
+                if self.precedes(child, best_child) {
+                    best_child = child;
+                }
+            }
+            if !self.precedes(best_child, index) {
+                break;
+            }
+            self.entries.swap(index, best_child);
+            index = best_child;
         }
     }
 
-    fn swap(&mut self, a: usize, b: usize) {
-        self.keys.swap(a, b);
-        self.aux.swap(a, b);
-    }
-
     /// Inserts an element into the heap.
     pub fn insert(&mut self, key: K, aux: T) {
-        let new_index = self.keys.len();
-        self.keys.push(key);
-        self.aux.push(aux);
-        self.ascend(new_index);
-        self.len += 1;
+        self.entries.push((key, aux));
+        self.ascend(self.entries.len() - 1);
+    }
+
+    /// Returns references to the first element (the minimum by default) if it exists without
+    /// removing it from the heap.
+    pub fn peek(&self) -> Option<(&K, &T)> {
+        self.entries.first().map(|(key, aux)| (key, aux))
     }
 
-    /// Returns the minimum element if it exists and removes it from the queue.
+    /// Returns the first element (the minimum by default) if it exists and removes it from the
+    /// queue.
     pub fn extract_min(&mut self) -> Option<(K, T)> {
-        if self.len == 0 {
+        if self.entries.is_empty() {
             return None;
         }
+        let result = self.entries.swap_remove(0);
+        self.descend(0, self.entries.len());
+        Some(result)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
 
-        let result = Some((self.keys[1], self.aux[1]));
+    /// Iterates over the elements in no particular order.
+    pub fn iter(&self) -> std::slice::Iter<'_, (K, T)> {
+        self.entries.iter()
+    }
 
-        self.keys.swap_remove(1);
-        self.aux.swap_remove(1);
-        self.descend(1);
+    /// Removes all the elements, returning them in no particular order.
+    pub fn drain(&mut self) -> std::vec::Drain<'_, (K, T)> {
+        self.entries.drain(..)
+    }
 
-        self.len -= 1;
+    /// Returns the elements in the order in which they would be extracted. The heap is sorted in
+    /// place, so no memory is allocated.
+    pub fn into_sorted_vec(mut self) -> Vec<(K, T)> {
+        // Moving the first element behind the shrinking heap leaves the elements in reverse order.
+        for end in (1..self.entries.len()).rev() {
+            self.entries.swap(0, end);
+            self.descend(0, end);
+        }
+        self.entries.reverse();
+        self.entries
+    }
+}
 
-        result
+impl<K, T, C> Heap<K, T, C>
+where
+    K: Copy,
+    T: Copy,
+    C: Comparator<K>,
+{
+    /// Returns the first element (the minimum by default) if it exists without removing it from
+    /// the heap.
+    pub fn min(&self) -> Option<(K, T)> {
+        self.entries.first().copied()
+    }
+}
+
+impl<K, T, C> From<Vec<(K, T)>> for Heap<K, T, C>
+where
+    C: Comparator<K> + Default,
+{
+    fn from(entries: Vec<(K, T)>) -> Self {
+        Self::from_vec_with_comparator(entries, C::default())
+    }
+}
+
+/// A binary min-heap of handles, which are indices smaller than the number of handles given on
+/// creation (e.g. the indices of the tiles of a grid). The heap remembers the position of every
+/// handle, so the key of a handle which is already in the heap can be decreased instead of
+/// inserting a duplicate which would have to be skipped when extracted.
+pub struct IndexedHeap<K> {
+    /// Keys together with their handles, so that moving an element touches a single entry.
+    entries: Vec<(K, usize)>,
+    /// The position of every handle in the heap, 0 if the handle is not in the heap.
+    positions: Vec<usize>,
+}
+
+impl<K> IndexedHeap<K>
+where
+    K: Default + Copy + Ord,
+{
+    /// Makes a heap for handles smaller than the given count. The entries start at index 1 after
+    /// a dummy element, so that position 0 can mark the handles which are not in the heap.
+    pub fn with_capacity(handle_count: usize) -> Self {
+        let mut entries = Vec::with_capacity(handle_count / 4 + 1);
+        entries.push((K::default(), 0));
+        Self {
+            entries,
+            positions: vec![0; handle_count],
+        }
+    }
+
+    /// Makes room for handles smaller than the given count.
+    pub fn reserve_handles(&mut self, handle_count: usize) {
+        if self.positions.len() < handle_count {
+            self.positions.resize(handle_count, 0);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() - 1
     }
 
     pub fn is_empty(&self) -> bool {
-        self.len == 0
+        self.len() == 0
+    }
+
+    pub fn contains(&self, handle: usize) -> bool {
+        self.positions[handle] != 0
+    }
+
+    /// Returns the key of the handle if it is in the heap.
+    pub fn key(&self, handle: usize) -> Option<K> {
+        let position = self.positions[handle];
+        (position != 0).then(|| self.entries[position].0)
+    }
+
+    /// Returns the minimum element if it exists without removing it from the heap.
+    pub fn min(&self) -> Option<(K, usize)> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.entries[1])
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.entries.swap(a, b);
+        self.positions[self.entries[a].1] = a;
+        self.positions[self.entries[b].1] = b;
+    }
+
+    fn ascend(&mut self, mut index: usize) {
+        while index > 1 && self.entries[index].0 < self.entries[index >> 1].0 {
+            self.swap(index, index >> 1);
+            index >>= 1;
+        }
     }
 
+    fn descend(&mut self, mut index: usize) {
+        let len = self.len();
+        let mut left_child = index << 1;
+        while left_child <= len {
+            let right_child = left_child + 1;
+            let min_child =
+                if right_child <= len && self.entries[left_child].0 > self.entries[right_child].0 {
+                    right_child
+                } else {
+                    left_child
+                };
+            if self.entries[index].0 <= self.entries[min_child].0 {
+                break;
+            }
+            self.swap(index, min_child);
+            index = min_child;
+            left_child = index << 1;
+        }
+    }
+
+    /// Inserts a handle which is not in the heap yet.
+    pub fn insert(&mut self, key: K, handle: usize) {
+        debug_assert!(!self.contains(handle), "The handle is already in the heap.");
+        self.entries.push((key, handle));
+        let position = self.len();
+        self.positions[handle] = position;
+        self.ascend(position);
+    }
+
+    /// Decreases the key of a handle which is in the heap. Keys which are not smaller than the
+    /// current one are ignored.
+    pub fn decrease_key(&mut self, handle: usize, key: K) {
+        let position = self.positions[handle];
+        debug_assert!(position != 0, "The handle is not in the heap.");
+        if key < self.entries[position].0 {
+            self.entries[position].0 = key;
+            self.ascend(position);
+        }
+    }
+
+    /// Removes the handle from the heap, returning its key if it was in the heap.
+    pub fn remove(&mut self, handle: usize) -> Option<K> {
+        let position = self.positions[handle];
+        if position == 0 {
+            return None;
+        }
+        let last = self.len();
+        self.swap(position, last);
+        let key = self.entries.pop().map(|(key, _)| key);
+        self.positions[handle] = 0;
+        if position < last {
+            self.ascend(position);
+            self.descend(position);
+        }
+        key
+    }
+
+    /// Returns the minimum element if it exists and removes it from the heap.
+    pub fn extract_min(&mut self) -> Option<(K, usize)> {
+        let (key, handle) = self.min()?;
+        self.remove(handle);
+        Some((key, handle))
+    }
+
+    /// Removes all elements. Takes time proportional to the number of elements in the heap rather
+    /// than the number of handles.
     pub fn clear(&mut self) {
-        self.keys.resize_with(1, Default::default);
-        self.aux.resize_with(1, Default::default);
-        self.len = 0;
+        for &(_, handle) in &self.entries[1..] {
+            self.positions[handle] = 0;
+        }
+        self.entries.truncate(1);
     }
 }
 
@@ -128,7 +379,7 @@ mod test {
         let result = heap.min().unwrap();
         assert_eq!(0, result.0, "Key is incorrect.");
         assert_eq!(0, result.0, "Additional value is incorrect.");
-        assert_eq!(heap.len, 3, "Size of the heap is incorrect.");
+        assert_eq!(heap.len(), 3, "Size of the heap is incorrect.");
         assert!(!heap.is_empty(), "Heap should not be empty.");
 
         // Perform checks again after clearing.
@@ -140,7 +391,7 @@ mod test {
         let result = heap.min().unwrap();
         assert_eq!(0, result.0, "Key is incorrect.");
         assert_eq!(0, result.0, "Additional value is incorrect.");
-        assert_eq!(heap.len, 3, "Size of the heap is incorrect.");
+        assert_eq!(heap.len(), 3, "Size of the heap is incorrect.");
         assert!(!heap.is_empty(), "Heap should not be empty.");
     }
 
@@ -153,10 +404,153 @@ mod test {
         let result = heap.extract_min().unwrap();
         assert_eq!(0, result.0, "Key is incorrect.");
         assert_eq!(0, result.0, "Additional value is incorrect.");
-        assert_eq!(heap.len, 2, "Size of the heap is incorrect.");
+        assert_eq!(heap.len(), 2, "Size of the heap is incorrect.");
         heap.extract_min().unwrap();
         heap.extract_min().unwrap();
         assert!(heap.is_empty(), "Heap should be empty.");
         assert_eq!(heap.min(), None, "Output of min should be None.");
     }
+
+    #[test]
+    fn heapify_and_sorted_vec() {
+        let keys = [5, 3, 8, 1, 9, 2, 7, 4, 6, 0];
+        let heap: Heap<usize, usize> = keys
+            .iter()
+            .map(|&key| (key, key * 10))
+            .collect::<Vec<_>>()
+            .into();
+        assert_eq!(heap.len(), 10, "Size of the heap is incorrect.");
+        assert_eq!(heap.peek(), Some((&0, &0)), "Minimum is incorrect.");
+        assert_eq!(
+            heap.into_sorted_vec(),
+            (0..10).map(|key| (key, key * 10)).collect::<Vec<_>>(),
+            "Elements should be sorted by their keys, keeping their payloads."
+        );
+    }
+
+    #[test]
+    fn comparators_and_arity() {
+        let keys = [5, 3, 8, 1, 9, 2, 7, 4, 6, 0];
+        for arity in 2..6 {
+            let mut max_heap = Heap::with_comparator(MaxFirst, 0).with_arity(arity);
+            // Order by the length of the payload, longest first, with a closure.
+            let mut by_length =
+                Heap::with_comparator(|a: &String, b: &String| a.len() > b.len(), keys.len())
+                    .with_arity(arity);
+            for key in keys {
+                max_heap.insert(key, key.to_string());
+                by_length.insert("x".repeat(key), key);
+            }
+
+            let order: Vec<usize> = std::iter::from_fn(|| max_heap.extract_min())
+                .map(|(key, _)| key)
+                .collect();
+            assert_eq!(
+                order,
+                vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+                "A max-heap of arity {arity} should extract the biggest keys first."
+            );
+            let order: Vec<usize> = by_length
+                .into_sorted_vec()
+                .into_iter()
+                .map(|(_, key)| key)
+                .collect();
+            assert_eq!(
+                order,
+                vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+                "The closure of arity {arity} should order the longest payloads first."
+            );
+        }
+    }
+
+    #[test]
+    fn iteration_and_drain() {
+        let mut heap: Heap<usize, String> =
+            Heap::from_vec_with_comparator(vec![(2, "b".into()), (1, "a".into())], MinFirst)
+                .with_arity(3);
+        heap.insert(0, "c".into());
+        let mut keys: Vec<usize> = heap.iter().map(|(key, _)| *key).collect();
+        keys.sort();
+        assert_eq!(keys, vec![0, 1, 2], "Iteration should visit every element.");
+        assert_eq!(
+            heap.peek(),
+            Some((&0, &"c".to_string())),
+            "Minimum is incorrect."
+        );
+
+        let mut drained: Vec<(usize, String)> = heap.drain().collect();
+        drained.sort();
+        assert_eq!(
+            drained,
+            vec![(0, "c".into()), (1, "a".into()), (2, "b".into())],
+            "Draining should return every element."
+        );
+        assert!(heap.is_empty(), "Heap should be empty after draining.");
+        assert_eq!(heap.peek(), None, "Output of peek should be None.");
+    }
+
+    #[test]
+    fn indexed_heap_decrease_key() {
+        let mut heap: IndexedHeap<usize> = IndexedHeap::with_capacity(8);
+        heap.insert(5, 0);
+        heap.insert(3, 1);
+        heap.insert(4, 2);
+        assert_eq!(heap.len(), 3, "Size of the heap is incorrect.");
+        assert!(heap.contains(2), "The handle should be in the heap.");
+        assert!(!heap.contains(3), "The handle should not be in the heap.");
+
+        heap.decrease_key(0, 1);
+        assert_eq!(heap.key(0), Some(1), "Key is incorrect.");
+        // Bigger keys are ignored.
+        heap.decrease_key(2, 10);
+        assert_eq!(heap.key(2), Some(4), "Key should not increase.");
+        assert_eq!(heap.min(), Some((1, 0)), "Minimum is incorrect.");
+
+        let mut order = vec![];
+        while let Some(element) = heap.extract_min() {
+            order.push(element);
+        }
+        assert_eq!(
+            order,
+            vec![(1, 0), (3, 1), (4, 2)],
+            "Elements should be extracted in order of their keys."
+        );
+        assert!(
+            !heap.contains(0),
+            "Extracted handles should not be in the heap."
+        );
+    }
+
+    #[test]
+    fn indexed_heap_remove_and_clear() {
+        let mut heap: IndexedHeap<usize> = IndexedHeap::with_capacity(0);
+        heap.reserve_handles(16);
+        for handle in 0..16 {
+            heap.insert((handle * 7) % 16, handle);
+        }
+        assert_eq!(heap.remove(3), Some(5), "Removed key is incorrect.");
+        assert_eq!(heap.remove(3), None, "The handle was already removed.");
+        assert_eq!(heap.remove(0), Some(0), "Removed key is incorrect.");
+
+        let mut keys = vec![];
+        while let Some((key, _)) = heap.extract_min() {
+            keys.push(key);
+        }
+        assert_eq!(keys.len(), 14, "Size of the heap is incorrect.");
+        assert!(
+            keys.windows(2).all(|pair| pair[0] <= pair[1]),
+            "The heap property should hold after removals."
+        );
+
+        heap.insert(1, 4);
+        heap.insert(2, 5);
+        heap.clear();
+        assert!(heap.is_empty(), "Heap should be empty.");
+        assert!(
+            !heap.contains(4),
+            "Cleared handles should not be in the heap."
+        );
+        heap.insert(3, 4);
+        assert_eq!(heap.min(), Some((3, 4)), "Minimum is incorrect.");
+    }
 }
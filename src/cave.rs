@@ -0,0 +1,229 @@
+use crate::Configuration;
+use crate::grid::{Grid, Tile, label_regions};
+use crate::maze::{connect_entrances, has_interior, ring_entrances};
+use crate::rng::Rng;
+use crate::room::{Dungeon, Room};
+
+/// Parameters of the cellular automaton which turns rooms into caves. Every tile of the room
+/// starts as a wall with the given chance. In every iteration an open tile becomes a wall when at
+/// least `birth` of its eight neighbors are walls and a wall stays a wall when at least `survival`
+/// of them are. Tiles outside the room count as walls.
+#[derive(Clone, Copy, Debug)]
+pub struct CellularCave {
+    /// What proportion of the rooms become caves.
+    pub chance: f32,
+    /// Percentage of the tiles which start as walls.
+    pub fill_percent: usize,
+    pub birth: usize,
+    pub survival: usize,
+    pub iterations: usize,
+}
+
+impl Default for CellularCave {
+    fn default() -> Self {
+        Self {
+            chance: 0.2,
+            fill_percent: 45,
+            birth: 5,
+            survival: 4,
+            iterations: 4,
+        }
+    }
+}
+
+/// Runs the cellular automaton on an area of the given dimensions. Tiles which are open from the
+/// start (e.g. the entrances of the room) stay open. Returns which tiles are walls.
+fn run_automaton<R: Rng>(
+    parameters: &CellularCave,
+    width: usize,
+    height: usize,
+    open: &[bool],
+    rng: &mut R,
+) -> Vec<bool> {
+    let mut walls: Vec<bool> = open
+        .iter()
+        .map(|&open| !open && rng.random_range(1..=100) <= parameters.fill_percent)
+        .collect();
+    let mut next = walls.clone();
+    for _ in 0..parameters.iterations {
+        for y in 0..height {
+            for x in 0..width {
+                let wall_neighbors = (y as isize - 1..=y as isize + 1)
+                    .flat_map(|ny| (x as isize - 1..=x as isize + 1).map(move |nx| (nx, ny)))
+                    .filter(|&(nx, ny)| (nx, ny) != (x as isize, y as isize))
+                    .filter(|&(nx, ny)| {
+                        nx < 0
+                            || ny < 0
+                            || nx as usize >= width
+                            || ny as usize >= height
+                            || walls[nx as usize + ny as usize * width]
+                    })
+                    .count();
+                let tile = x + y * width;
+                next[tile] = !open[tile]
+                    && if walls[tile] {
+                        wall_neighbors >= parameters.survival
+                    } else {
+                        wall_neighbors >= parameters.birth
+                    };
+            }
+        }
+        std::mem::swap(&mut walls, &mut next);
+    }
+    walls
+}
+
+/// Turns the room into a cave with the cellular automaton. Only the biggest region of open tiles
+/// is kept and the entrances of the room (tiles next to its used doorways, given as grid indices)
+/// are connected to it, so the cave is a single region which can be entered through every
+/// doorway.
+pub fn place_cave<R: Rng>(
+    rng: &mut R,
+    parameters: &CellularCave,
+    room: &Room,
+    entrances: &[usize],
+    grid: &mut Grid,
+) {
+    let bounds = room.bounds;
+    let to_grid = |tile: usize| {
+        bounds.x + tile % bounds.width + (bounds.y + tile / bounds.width) * grid.width
+    };
+    let open: Vec<bool> = (0..bounds.width * bounds.height)
+        .map(|tile| grid.tiles[to_grid(tile)] != Tile::Room)
+        .collect();
+    let mut walls = run_automaton(parameters, bounds.width, bounds.height, &open, rng);
+
+    let (labels, sizes) = label_regions(bounds.width, bounds.height, &walls);
+    let biggest = (0..sizes.len()).max_by_key(|&label| sizes[label]);
+    for (tile, &label) in labels.iter().enumerate() {
+        if Some(label) != biggest {
+            walls[tile] = true;
+        }
+    }
+    for (tile, &wall) in walls.iter().enumerate() {
+        let grid_tile = to_grid(tile);
+        if wall && grid.tiles[grid_tile] == Tile::Room {
+            grid.tiles[grid_tile] = Tile::Wall;
+        }
+    }
+
+    // Connect the entrances to a tile of the biggest region.
+    let cave_tile = biggest
+        .and_then(|biggest| labels.iter().position(|&label| label == biggest))
+        .map(to_grid);
+    let targets: Vec<usize> = cave_tile
+        .into_iter()
+        .chain(entrances.iter().copied())
+        .collect();
+    connect_entrances(room, &targets, grid);
+}
+
+/// Turns a proportion of the rooms into caves. Rooms which already have walls inside (e.g.
+/// prefabs) are skipped. Runs before the mazes, which are not placed in caves.
+pub fn make_caves<R: Rng>(
+    rng: &mut R,
+    configuration: &Configuration,
+    grid: &mut Grid,
+    dungeon: &Dungeon,
+) {
+    let Some(parameters) = &configuration.cellular_cave else {
+        return;
+    };
+    for room in &dungeon.rooms {
+        if !has_interior(grid, room) && rng.random_float() < parameters.chance {
+            let entrances = ring_entrances(grid, room);
+            place_cave(rng, parameters, room, &entrances, grid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::make_grid;
+    use crate::mock::MockMinRng;
+    use crate::mst::pick_corridors;
+    use crate::room::generate_rooms;
+    use crate::triangulation::triangulate;
+    use crate::vec::vec2u;
+    use crate::verify::verify;
+
+    use rand::SeedableRng;
+
+    #[test]
+    fn automaton_rules() {
+        // Without initial walls, only the corners have enough wall neighbors (outside the area)
+        // to give birth to walls.
+        let parameters = CellularCave {
+            fill_percent: 0,
+            ..Default::default()
+        };
+        let walls = run_automaton(&parameters, 7, 5, &[false; 35], &mut MockMinRng);
+        let wall_tiles: Vec<usize> = (0..35).filter(|&tile| walls[tile]).collect();
+        assert_eq!(
+            wall_tiles,
+            vec![0, 6, 28, 34],
+            "Only the corners should become walls."
+        );
+
+        // Tiles which are open from the start stay open even when everything else is a wall.
+        let parameters = CellularCave {
+            fill_percent: 100,
+            ..Default::default()
+        };
+        let mut open = [false; 35];
+        open[17] = true;
+        let walls = run_automaton(&parameters, 7, 5, &open, &mut MockMinRng);
+        assert!(!walls[17], "The open tile should stay open.");
+        assert_eq!(
+            walls.iter().filter(|&&wall| wall).count(),
+            34,
+            "Every other tile should stay a wall."
+        );
+    }
+
+    #[test]
+    fn caves_are_connected() {
+        let configuration = Configuration {
+            cellular_cave: Some(CellularCave {
+                chance: 1.0,
+                ..Default::default()
+            }),
+            maze_chance: 1.0,
+            ..Default::default()
+        };
+        let grid_dimensions = vec2u(100, 100);
+        for seed in 0..10 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut dungeon = generate_rooms(&configuration, grid_dimensions, Some(20), &mut rng);
+            let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+            let corridors = pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+            let (mut grid, _) = make_grid(&configuration, grid_dimensions, &dungeon, &corridors)
+                .expect("The corridors should be carved.");
+            make_caves(&mut rng, &configuration, &mut grid, &dungeon);
+            let caves = grid.tiles.clone();
+            // Mazes are not placed in caves.
+            crate::maze::make_mazes(&mut rng, &configuration, &mut grid, &dungeon);
+            assert_eq!(grid.tiles, caves, "Mazes should not be placed in caves.");
+
+            let verification = verify(&grid, &dungeon);
+            assert!(
+                verification.is_valid(&configuration),
+                "Caves with seed {seed} have problems: {verification:?}"
+            );
+            for room in &dungeon.rooms {
+                let bounds = room.bounds;
+                let walls: Vec<bool> = grid
+                    .region_tiles(bounds)
+                    .map(|(_, tile)| tile == Tile::Wall)
+                    .collect();
+                let (_, sizes) = label_regions(bounds.width, bounds.height, &walls);
+                assert_eq!(
+                    sizes.len(),
+                    1,
+                    "Every cave should be a single region (seed {seed})."
+                );
+            }
+        }
+    }
+}
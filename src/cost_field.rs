@@ -0,0 +1,98 @@
+use crate::rng::Rng;
+use crate::vec::{Rectangle, Vector2};
+
+/// Generates a smooth random cost field for corridor routing with value noise. Random values
+/// between 0 and the amplitude are placed at the corners of a lattice of square cells of the given
+/// size and the value of every tile is interpolated between the corners of its cell. Bigger cells
+/// give wider and more gradual hills which the corridors wind around.
+pub fn value_noise<R: Rng>(
+    grid_dimensions: Vector2,
+    cell_size: usize,
+    amplitude: usize,
+    rng: &mut R,
+) -> Vec<usize> {
+    let width = grid_dimensions.x as usize;
+    let height = grid_dimensions.y as usize;
+    let cell_size = cell_size.max(1);
+
+    // One more corner than cells in each direction and one more cell for the remainder.
+    let lattice_width = width / cell_size + 2;
+    let lattice_height = height / cell_size + 2;
+    let lattice: Vec<usize> = (0..lattice_width * lattice_height)
+        .map(|_| rng.random_range(0..=amplitude))
+        .collect();
+
+    let mut field = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (cell_y, offset_y) = (y / cell_size, y % cell_size);
+        for x in 0..width {
+            let (cell_x, offset_x) = (x / cell_size, x % cell_size);
+            let corner =
+                |dx: usize, dy: usize| lattice[cell_x + dx + (cell_y + dy) * lattice_width];
+            // Bilinear interpolation in integers, dividing only at the end.
+            let top = corner(0, 0) * (cell_size - offset_x) + corner(1, 0) * offset_x;
+            let bottom = corner(0, 1) * (cell_size - offset_x) + corner(1, 1) * offset_x;
+            field
+                .push((top * (cell_size - offset_y) + bottom * offset_y) / (cell_size * cell_size));
+        }
+    }
+    field
+}
+
+/// Adds a cost to every tile of the area, e.g. to make the corridors avoid a lake. The area is
+/// clipped to the field.
+pub fn add_area_cost(field: &mut [usize], width: usize, area: &Rectangle, cost: usize) {
+    let height = field.len() / width;
+    for y in area.y..(area.y + area.height).min(height) {
+        for x in area.x..(area.x + area.width).min(width) {
+            field[x + y * width] += cost;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::MockRng;
+    use crate::vec::vec2u;
+
+    #[test]
+    fn value_noise_interpolates() {
+        // The lattice of a 4x4 grid with cells of size 4 has 3x3 corners.
+        let mut rng = MockRng::new(vec![0, 8, 0, 8, 0, 0, 0, 0, 0]);
+        let field = value_noise(vec2u(4, 4), 4, 8, &mut rng);
+        assert_eq!(field.len(), 16, "Every tile should have a cost.");
+        assert_eq!(
+            &field[0..4],
+            &[0, 2, 4, 6],
+            "The first row should go from the first corner to the second one."
+        );
+        assert_eq!(
+            &field[4..8],
+            &[2, 3, 4, 5],
+            "The second row should be interpolated between the rows of corners."
+        );
+        assert!(
+            field.iter().all(|&cost| cost <= 8),
+            "Costs should not exceed the amplitude."
+        );
+    }
+
+    #[test]
+    fn area_cost() {
+        let mut field = vec![1; 16];
+        add_area_cost(&mut field, 4, &Rectangle::new(1, 1, 2, 2), 5);
+        assert_eq!(
+            field,
+            vec![1, 1, 1, 1, 1, 6, 6, 1, 1, 6, 6, 1, 1, 1, 1, 1],
+            "Only the tiles of the area should be more expensive."
+        );
+        // The area reaches past the south-east corner of the field.
+        add_area_cost(&mut field, 4, &Rectangle::new(3, 2, 5, 5), 1);
+        assert_eq!(
+            field,
+            vec![1, 1, 1, 1, 1, 6, 6, 1, 1, 6, 6, 2, 1, 1, 1, 2],
+            "The area should be clipped to the field."
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use crate::Configuration;
+use crate::a_star::Pathfinder;
+use crate::grid::{
+    Corridor, CorridorError, CorridorFailure, CorridorReport, Grid, Recovery, Tile, carve_rooms,
+    count_turns, disconnected_rooms,
+};
+use crate::rng::Rng;
+use crate::room::{Dungeon, Edges};
+use crate::vec::{self, Vector2};
+
+use std::collections::HashMap;
+
+/// Parameters of the random walk ("drunkard") corridor carver.
+#[derive(Clone, Copy, Debug)]
+pub struct DrunkardWalk {
+    /// Percentage of the steps which go towards the target. The rest go in a random direction.
+    pub bias: usize,
+    /// The walk may take this many steps for every tile of the Manhattan distance between the
+    /// doorways. After that the rest of the corridor is carved with the A* algorithm, so the walk
+    /// always terminates.
+    pub patience: usize,
+}
+
+impl Default for DrunkardWalk {
+    fn default() -> Self {
+        Self {
+            bias: 60,
+            patience: 8,
+        }
+    }
+}
+
+/// Answers whether the random walk can step on the tile.
+fn is_walkable(tile: Tile) -> bool {
+    !matches!(tile, Tile::Blocker | Tile::Room)
+}
+
+/// Walks randomly from the start towards the end, preferring the steps which get closer to the
+/// end. Returns the visited tiles in order, which may repeat. The walk stops at the end or when it
+/// runs out of patience.
+fn random_walk<R: Rng>(
+    parameters: &DrunkardWalk,
+    start: usize,
+    end: usize,
+    width: usize,
+    tiles: &[Tile],
+    rng: &mut R,
+) -> Vec<usize> {
+    let (end_x, end_y) = (end % width, end / width);
+    let distance = (start % width).abs_diff(end_x) + (start / width).abs_diff(end_y);
+    let mut walk = vec![start];
+    let mut current = start;
+
+    for _ in 0..parameters.patience * distance {
+        if current == end {
+            break;
+        }
+        let (x, y) = (current % width, current / width);
+        // The perimeter of blockers around the grid keeps the neighbors inside of it.
+        let neighbors: Vec<usize> = [current + width, current - width, current + 1, current - 1]
+            .into_iter()
+            .filter(|&neighbor| is_walkable(tiles[neighbor]))
+            .collect();
+        let closer: Vec<usize> = neighbors
+            .iter()
+            .copied()
+            .filter(|&neighbor| {
+                let (neighbor_x, neighbor_y) = (neighbor % width, neighbor / width);
+                neighbor_x.abs_diff(end_x) < x.abs_diff(end_x)
+                    || neighbor_y.abs_diff(end_y) < y.abs_diff(end_y)
+            })
+            .collect();
+
+        let candidates = if !closer.is_empty() && rng.random_range(1..=100) <= parameters.bias {
+            closer
+        } else {
+            neighbors
+        };
+        if candidates.is_empty() {
+            break;
+        }
+        current = candidates[rng.random_range(0..=candidates.len() - 1)];
+        walk.push(current);
+    }
+
+    walk
+}
+
+/// Removes the loops from a walk, leaving a path without repeated tiles.
+fn erase_loops(walk: &[usize]) -> Vec<usize> {
+    let mut path: Vec<usize> = vec![];
+    let mut indices: HashMap<usize, usize> = HashMap::new();
+    for &tile in walk {
+        if let Some(&index) = indices.get(&tile) {
+            for removed in path.drain(index + 1..) {
+                indices.remove(&removed);
+            }
+        } else {
+            indices.insert(tile, path.len());
+            path.push(tile);
+        }
+    }
+    path
+}
+
+/// Carves a single corridor between two doorways with a random walk, finishing it with the A*
+/// algorithm if the walk runs out of patience. If there is no way to connect the doorways, the
+/// tiles of the doorways are restored.
+fn try_walk_corridor<R: Rng>(
+    configuration: &Configuration,
+    dungeon: &Dungeon,
+    edge: (usize, usize),
+    width: usize,
+    tiles: &mut [Tile],
+    pathfinder: &mut Pathfinder,
+    rng: &mut R,
+) -> Option<Corridor> {
+    let parameters = configuration.drunkard_walk.unwrap_or_default();
+    let start = vec::to_index(dungeon.doorways[edge.0].position, width);
+    let end = vec::to_index(dungeon.doorways[edge.1].position, width);
+    let previous_tiles = (tiles[start], tiles[end]);
+    tiles[start] = Tile::Doorway;
+    tiles[end] = Tile::Doorway;
+
+    let mut walk = random_walk(&parameters, start, end, width, tiles, rng);
+    let last = walk[walk.len() - 1];
+    if last != end {
+        // The walk carves irregular tunnels anyway, so squares are allowed.
+        let finish_configuration = Configuration {
+            disallow_corridor_squares: false,
+            diagonal_corridors: false,
+            ..configuration.clone()
+        };
+        let finish = pathfinder.find_path(&finish_configuration, last, end, width, tiles);
+        if finish.is_empty() {
+            tiles[start] = previous_tiles.0;
+            tiles[end] = previous_tiles.1;
+            return None;
+        }
+        // The path goes from the end to the last tile of the walk.
+        walk.extend(finish.iter().rev().skip(1));
+    }
+
+    let path = erase_loops(&walk);
+    let corridor = Corridor {
+        doorways: edge,
+        width: 1,
+        turns: count_turns(&path),
+        shared_tiles: path
+            .iter()
+            .copied()
+            .filter(|&tile| tiles[tile] == Tile::Corridor)
+            .collect(),
+        path,
+    };
+
+    for &tile in &walk {
+        if !matches!(tiles[tile], Tile::Doorway) {
+            tiles[tile] = Tile::Corridor;
+        }
+    }
+    // Place doorway marker tiles inside the rooms to help the maze generation procedure.
+    for doorway in [start, end] {
+        for neighbor in [doorway + width, doorway - width, doorway + 1, doorway - 1] {
+            if matches!(tiles[neighbor], Tile::Room) {
+                tiles[neighbor] = Tile::Doorway;
+            }
+        }
+    }
+
+    Some(corridor)
+}
+
+/// Creates the grid like make_grid, but carves the corridors with biased random walks towards the
+/// other doorway, which gives irregular, cave-like tunnels. Like the corridors carved by the A*
+/// algorithm, the tunnels never go through the blockers around the grid and the rooms. A walk
+/// which runs out of patience is finished with the A* algorithm, so the doorways are always
+/// connected if it is possible. The parameters of the walk are taken from the configuration.
+pub fn make_cave_grid<R: Rng>(
+    configuration: &Configuration,
+    grid_dimensions: Vector2,
+    dungeon: &Dungeon,
+    corridors: &Edges,
+    rng: &mut R,
+) -> Result<(Grid, CorridorReport), CorridorError> {
+    let width = grid_dimensions.x as usize;
+    let mut tiles = carve_rooms(grid_dimensions, dungeon);
+    let mut pathfinder = Pathfinder::new(tiles.len());
+
+    let mut report = CorridorReport::default();
+    for &edge in corridors {
+        match try_walk_corridor(
+            configuration,
+            dungeon,
+            edge,
+            width,
+            &mut tiles,
+            &mut pathfinder,
+            rng,
+        ) {
+            Some(corridor) => report.corridors.push(corridor),
+            None => report.failures.push(CorridorFailure {
+                edge,
+                recovery: Recovery::Failed,
+            }),
+        }
+    }
+
+    let grid = Grid { width, tiles };
+    let disconnected_rooms = disconnected_rooms(dungeon, corridors, &report);
+    if disconnected_rooms.is_empty() {
+        Ok((grid, report))
+    } else {
+        Err(CorridorError {
+            grid,
+            report,
+            disconnected_rooms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{MockMinRng, doorway, room};
+    use crate::vec::{to_index, vec2u};
+    use crate::verify::verify;
+
+    use rand::SeedableRng;
+
+    #[test]
+    fn loop_erasure() {
+        assert_eq!(
+            erase_loops(&[1, 2, 3, 2, 4, 5, 4, 1, 6]),
+            vec![1, 6],
+            "Every loop should be removed."
+        );
+    }
+
+    #[test]
+    fn biased_walk() {
+        let Grid { width, tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %#########%\n\
+            %d#######d%\n\
+            %#########%\n\
+            %%%%%%%%%%%\n",
+        );
+        let start = to_index(vec2u(1, 2), width);
+        let end = to_index(vec2u(9, 2), width);
+        // The minimum always takes a step towards the end.
+        let walk = random_walk(
+            &DrunkardWalk::default(),
+            start,
+            end,
+            width,
+            &tiles,
+            &mut MockMinRng,
+        );
+        assert_eq!(walk.len(), 9, "The walk should go straight to the end.");
+
+        // Without patience the walk does not move at all.
+        let parameters = DrunkardWalk {
+            patience: 0,
+            ..Default::default()
+        };
+        let walk = random_walk(&parameters, start, end, width, &tiles, &mut MockMinRng);
+        assert_eq!(walk, vec![start], "The walk should not move.");
+    }
+
+    #[test]
+    fn walk_is_finished_by_a_star() {
+        let configuration = Configuration {
+            drunkard_walk: Some(DrunkardWalk {
+                bias: 0,
+                patience: 0,
+            }),
+            ..Default::default()
+        };
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![doorway(1, 2, 0), doorway(9, 2, 1)],
+        };
+        let mut tiles = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %#########%\n\
+            %%####%##%%\n\
+            %####%####%\n\
+            %%%%%%%%%%%\n",
+        )
+        .tiles;
+        let width = 11;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let corridor = try_walk_corridor(
+            &configuration,
+            &dungeon,
+            (0, 1),
+            width,
+            &mut tiles,
+            &mut Pathfinder::new(0),
+            &mut rng,
+        )
+        .expect("The doorways should be connected.");
+        assert_eq!(
+            corridor.path.first(),
+            Some(&to_index(vec2u(1, 2), width)),
+            "The path should start at the first doorway."
+        );
+        assert_eq!(
+            corridor.path.last(),
+            Some(&to_index(vec2u(9, 2), width)),
+            "The path should end at the second doorway."
+        );
+    }
+
+    #[test]
+    fn cave_grids_are_connected() {
+        use crate::{mst::pick_corridors, room::generate_rooms, triangulation::triangulate};
+
+        let configuration = Configuration {
+            drunkard_walk: Some(DrunkardWalk::default()),
+            disallow_corridor_squares: false,
+            ..Default::default()
+        };
+        let grid_dimensions = vec2u(80, 80);
+        for seed in 0..10 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut dungeon = generate_rooms(&configuration, grid_dimensions, Some(15), &mut rng);
+            let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+            let corridors = pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+            let (grid, _) = make_cave_grid(
+                &configuration,
+                grid_dimensions,
+                &dungeon,
+                &corridors,
+                &mut rng,
+            )
+            .expect("The corridors should be carved.");
+
+            let verification = verify(&grid, &dungeon);
+            assert!(
+                verification.is_valid(&configuration),
+                "Cave grid with seed {seed} has problems: {verification:?}"
+            );
+            let width = grid.width;
+            let height = grid.height();
+            assert!(
+                (0..grid.tiles.len())
+                    .filter(|tile| {
+                        let (x, y) = (tile % width, tile / width);
+                        x == 0 || y == 0 || x == width - 1 || y == height - 1
+                    })
+                    .all(|tile| grid.tiles[tile] == Tile::Blocker),
+                "The perimeter of the grid with seed {seed} should stay intact."
+            );
+        }
+    }
+
+    #[test]
+    fn rooms_are_not_carved() {
+        let configuration = Configuration::default();
+        let dungeon = Dungeon {
+            rooms: vec![room(2, 2, 5, 5), room(12, 2, 5, 5)],
+            doorways: vec![doorway(7, 4, 0), doorway(11, 4, 1)],
+        };
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let (grid, report) = make_cave_grid(
+            &configuration,
+            vec2u(20, 9),
+            &dungeon,
+            &vec![(0, 1)],
+            &mut rng,
+        )
+        .expect("The rooms should be connected.");
+        assert_eq!(report.corridors.len(), 1, "Corridor count is incorrect.");
+        for room in &dungeon.rooms {
+            for y in room.bounds.y..room.bounds.y + room.bounds.height {
+                for x in room.bounds.x..room.bounds.x + room.bounds.width {
+                    assert_ne!(
+                        grid.tiles[to_index(vec2u(x, y), grid.width)],
+                        Tile::Corridor,
+                        "The walk should not enter the rooms."
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use crate::Configuration;
-use crate::a_star;
-use crate::binary_heap::Heap;
+use crate::a_star::Pathfinder;
+use crate::mst::DisjointSet;
 use crate::room::{Dungeon, Edges};
-use crate::vec::{self, Vector2};
+use crate::vec::{self, Rectangle, Vector2};
 
+use std::collections::HashSet;
 use std::fmt::Write;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -17,22 +18,41 @@ pub enum Tile {
     Empty,
 }
 
-/// Convert a character to a tile.
-impl From<char> for Tile {
-    fn from(value: char) -> Self {
+impl Tile {
+    /// All the types of tiles.
+    pub const ALL: [Tile; 7] = [
+        Tile::Blocker,
+        Tile::Wall,
+        Tile::Room,
+        Tile::Doorway,
+        Tile::Corridor,
+        Tile::CorridorNeighbor,
+        Tile::Empty,
+    ];
+
+    /// Converts a character to a tile. Returns None for characters which do not represent any.
+    pub fn from_char(value: char) -> Option<Self> {
         use Tile::*;
         match value {
-            '%' => Blocker,
-            '#' => Wall,
-            '_' => Room,
-            'd' => Doorway,
-            'c' => Corridor,
-            '@' => CorridorNeighbor,
-            _ => Empty,
+            '%' => Some(Blocker),
+            '#' => Some(Wall),
+            '_' => Some(Room),
+            'd' => Some(Doorway),
+            'c' => Some(Corridor),
+            '@' => Some(CorridorNeighbor),
+            '.' => Some(Empty),
+            _ => None,
         }
     }
 }
 
+/// Convert a character to a tile. Unknown characters become empty tiles.
+impl From<char> for Tile {
+    fn from(value: char) -> Self {
+        Tile::from_char(value).unwrap_or(Tile::Empty)
+    }
+}
+
 /// Convert a tile to a character.
 impl From<Tile> for char {
     fn from(value: Tile) -> Self {
@@ -49,7 +69,7 @@ impl From<Tile> for char {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Grid {
     pub width: usize,
     pub tiles: Vec<Tile>,
@@ -59,7 +79,7 @@ pub struct Grid {
 impl std::fmt::Display for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut index = 0;
-        for _ in 0..self.tiles.len() / self.width {
+        for _ in 0..self.tiles.len().checked_div(self.width).unwrap_or(0) {
             for _ in 0..self.width {
                 f.write_char(self.tiles[index].into())?;
                 index += 1;
@@ -70,31 +90,269 @@ impl std::fmt::Display for Grid {
     }
 }
 
-/// Convert a string into a grid. Assumes the string is a valid grid,
-/// however, it does not error or panic if it isn't.
-impl From<&str> for Grid {
-    fn from(value: &str) -> Self {
-        let mut height: usize = 0;
-        let mut result = Grid {
-            width: 0,
-            tiles: vec![],
-        };
-        for line in value.lines() {
-            for character in line.chars() {
-                result.tiles.push(character.into());
+/// Returned when a string is not a valid grid. Lines and columns are counted from 1.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GridParseError {
+    /// The string contains no tiles.
+    Empty,
+    UnknownCharacter {
+        line: usize,
+        column: usize,
+        character: char,
+    },
+    /// The line is not as long as the first one.
+    InconsistentLength {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl std::fmt::Display for GridParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GridParseError::Empty => write!(f, "grid is empty"),
+            GridParseError::UnknownCharacter {
+                line,
+                column,
+                character,
+            } => write!(
+                f,
+                "unknown tile {character:?} at line {line}, column {column}"
+            ),
+            GridParseError::InconsistentLength {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line} has {found} tiles instead of {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for GridParseError {}
+
+/// Convert a string into a grid. Every line must have the same number of tiles.
+impl std::str::FromStr for Grid {
+    type Err = GridParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut width = None;
+        let mut tiles = vec![];
+        for (line_index, line) in value.lines().enumerate() {
+            let mut length = 0;
+            for (column_index, character) in line.chars().enumerate() {
+                let tile = Tile::from_char(character).ok_or(GridParseError::UnknownCharacter {
+                    line: line_index + 1,
+                    column: column_index + 1,
+                    character,
+                })?;
+                tiles.push(tile);
+                length += 1;
+            }
+            match width {
+                None => width = Some(length),
+                Some(expected) if expected != length => {
+                    return Err(GridParseError::InconsistentLength {
+                        line: line_index + 1,
+                        expected,
+                        found: length,
+                    });
+                }
+                _ => {}
             }
-            height += 1;
         }
-        result.width = result.tiles.len() / height;
-        result
+        match width {
+            Some(width) if width > 0 => Ok(Grid { width, tiles }),
+            _ => Err(GridParseError::Empty),
+        }
+    }
+}
+
+impl TryFrom<&str> for Grid {
+    type Error = GridParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
     }
 }
 
+impl Grid {
+    /// Converts a string into a grid without failing, for quick tests. Unknown characters become
+    /// empty tiles, lines shorter than the longest one are padded with empty tiles and an empty
+    /// string gives a grid without tiles.
+    pub fn parse_lenient(value: &str) -> Self {
+        let width = value
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        Grid {
+            width,
+            tiles: value
+                .lines()
+                .flat_map(|line| {
+                    line.chars()
+                        .map(Tile::from)
+                        .chain(std::iter::repeat(Tile::Empty))
+                        .take(width)
+                })
+                .collect(),
+        }
+    }
+
+    /// The number of rows of the grid.
+    pub fn height(&self) -> usize {
+        self.tiles.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// Converts coordinates into an index of the tiles. Returns None outside the grid.
+    pub fn to_index(&self, x: usize, y: usize) -> Option<usize> {
+        (x < self.width && y < self.height()).then(|| x + y * self.width)
+    }
+
+    /// Converts an index of the tiles into coordinates.
+    pub fn to_coordinates(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Tile> {
+        self.to_index(x, y).map(|index| &self.tiles[index])
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Tile> {
+        self.to_index(x, y).map(|index| &mut self.tiles[index])
+    }
+
+    /// The coordinates of the orthogonal neighbors of the tile which are inside the grid.
+    pub fn neighbors4(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + use<> {
+        self.neighbors(x, y, &[(1, 0), (0, -1), (-1, 0), (0, 1)])
+    }
+
+    /// The coordinates of the orthogonal and diagonal neighbors of the tile which are inside the
+    /// grid.
+    pub fn neighbors8(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + use<> {
+        self.neighbors(
+            x,
+            y,
+            &[
+                (1, 0),
+                (1, -1),
+                (0, -1),
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        )
+    }
+
+    fn neighbors(
+        &self,
+        x: usize,
+        y: usize,
+        offsets: &'static [(isize, isize)],
+    ) -> impl Iterator<Item = (usize, usize)> + use<> {
+        let (width, height) = (self.width, self.height());
+        offsets.iter().filter_map(move |&(dx, dy)| {
+            let (nx, ny) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+            (nx < width && ny < height).then_some((nx, ny))
+        })
+    }
+
+    /// Iterates over the rows of the grid from the top.
+    pub fn rows(&self) -> std::slice::Chunks<'_, Tile> {
+        self.tiles.chunks(self.width.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> std::slice::ChunksMut<'_, Tile> {
+        self.tiles.chunks_mut(self.width.max(1))
+    }
+
+    /// The coordinates of the tiles of the rectangle row by row, clipped to the grid.
+    pub fn region(&self, bounds: Rectangle) -> impl Iterator<Item = (usize, usize)> + use<> {
+        let right = (bounds.x + bounds.width).min(self.width);
+        let bottom = (bounds.y + bounds.height).min(self.height());
+        (bounds.y..bottom).flat_map(move |y| (bounds.x..right).map(move |x| (x, y)))
+    }
+
+    /// The coordinates and the tiles of the rectangle row by row, clipped to the grid.
+    pub fn region_tiles(
+        &self,
+        bounds: Rectangle,
+    ) -> impl Iterator<Item = ((usize, usize), Tile)> + '_ {
+        self.region(bounds)
+            .map(|position| (position, self[position]))
+    }
+}
+
+/// Indexes the grid with (x, y) coordinates. Panics outside the grid, even when the index of the
+/// tiles would be valid.
+impl std::ops::Index<(usize, usize)> for Grid {
+    type Output = Tile;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        let (width, height) = (self.width, self.height());
+        self.get(x, y)
+            .unwrap_or_else(|| panic!("({x}, {y}) is outside the {width}x{height} grid"))
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for Grid {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        let (width, height) = (self.width, self.height());
+        self.get_mut(x, y)
+            .unwrap_or_else(|| panic!("({x}, {y}) is outside the {width}x{height} grid"))
+    }
+}
+
+/// Labels the connected open tiles of an area of the given dimensions, given row by row. Returns
+/// the labels (usize::MAX for walls) and the size of every region.
+pub(crate) fn label_regions(
+    width: usize,
+    height: usize,
+    walls: &[bool],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut labels = vec![usize::MAX; walls.len()];
+    let mut sizes = vec![];
+    let mut stack = vec![];
+    for start in 0..walls.len() {
+        if walls[start] || labels[start] != usize::MAX {
+            continue;
+        }
+        let label = sizes.len();
+        sizes.push(0);
+        labels[start] = label;
+        stack.push(start);
+        while let Some(tile) = stack.pop() {
+            sizes[label] += 1;
+            let (x, y) = (tile % width, tile / width);
+            for (valid, neighbor) in [
+                (x + 1 < width, tile + 1),
+                (x > 0, tile.wrapping_sub(1)),
+                (y + 1 < height, tile + width),
+                (y > 0, tile.wrapping_sub(width)),
+            ] {
+                if valid && !walls[neighbor] && labels[neighbor] == usize::MAX {
+                    labels[neighbor] = label;
+                    stack.push(neighbor);
+                }
+            }
+        }
+    }
+    (labels, sizes)
+}
+
 /// Places a corridor in the grid. Surrounds the corridor with marker tiles
 /// so that the search algorithm knows to avoid creating 2x2 corridor tile blocks.
 /// When there is a turn in the corridor, places a blocking tile again to prevent
 /// the pathfinding algorithm from creating 2x2 corridor tile blocks.
-fn place_corridor(disallow_corridor_squares: bool, width: usize, tiles: &mut [Tile], path: &[usize]) {
+fn place_corridor(
+    disallow_corridor_squares: bool,
+    width: usize,
+    tiles: &mut [Tile],
+    path: &[usize],
+) {
     use Tile::*;
 
     #[inline]
@@ -133,56 +391,585 @@ fn place_corridor(disallow_corridor_squares: bool, width: usize, tiles: &mut [Ti
     }
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Turns a path with diagonal steps into one which can be walked with orthogonal steps only, by
+/// adding one of the two corner tiles of every diagonal step. A corner which is already a
+/// corridor is preferred, then one which does not make a 2x2 square with the corridors and the
+/// tiles of the path. Returns None if both corners of a step make a square and squares are
+/// disallowed.
+fn orthogonal_path(
+    disallow_corridor_squares: bool,
+    width: usize,
+    tiles: &[Tile],
+    path: &[usize],
+) -> Option<Vec<usize>> {
+    let mut carved: HashSet<usize> = path.iter().copied().collect();
+    let makes_square = |carved: &HashSet<usize>, corner: usize| {
+        let is_corridor =
+            |tile: usize| tile == corner || carved.contains(&tile) || tiles[tile] == Tile::Corridor;
+        [corner - width - 1, corner - width, corner - 1, corner]
+            .iter()
+            .any(|&northwest| {
+                [
+                    northwest,
+                    northwest + 1,
+                    northwest + width,
+                    northwest + width + 1,
+                ]
+                .iter()
+                .all(|&tile| is_corridor(tile))
+            })
+    };
+
+    let mut orthogonal = Vec::with_capacity(path.len() * 2);
+    orthogonal.extend(path.first());
+    for step in path.windows(2) {
+        let (previous, current) = (step[0], step[1]);
+        let distance = previous.abs_diff(current);
+        if distance == width + 1 || distance == width - 1 {
+            // The corners share a row with one of the tiles and a column with the other.
+            let corners = [
+                previous - previous % width + current % width,
+                current - current % width + previous % width,
+            ];
+            let corner = corners
+                .iter()
+                .copied()
+                .find(|&corner| tiles[corner] == Tile::Corridor)
+                .or_else(|| {
+                    corners
+                        .iter()
+                        .copied()
+                        .find(|&corner| !makes_square(&carved, corner))
+                })
+                .or((!disallow_corridor_squares).then_some(corners[0]))?;
+            carved.insert(corner);
+            orthogonal.push(corner);
+        }
+        orthogonal.push(current);
+    }
+    Some(orthogonal)
+}
+
+/// A limit on the shape of the corridors set in the configuration.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShapeLimit {
+    /// The maximum number of turns.
+    Turns,
+    /// The maximum length relative to the Manhattan distance between the doorways.
+    Length,
+}
+
+/// How the generator dealt with a corridor edge which the pathfinding algorithm could not carve
+/// or which broke one of the shape limits.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Recovery {
+    /// The corridor was carved with the default costs and 2x2 corridor squares allowed.
+    Relaxed,
+    /// The two rooms were connected through a different pair of doorways given by the edge.
+    Rerouted { edge: (usize, usize), relaxed: bool },
+    /// Every way to carve the corridor broke the limit, but the two rooms are connected through
+    /// other corridors, so it was not carved.
+    Dropped { limit: ShapeLimit },
+    /// Every way to carve the corridor broke the limit, but it was carved anyway since it is
+    /// needed to connect the two rooms.
+    Unlimited { limit: ShapeLimit },
+    /// The two rooms could not be connected directly.
+    Failed,
+}
+
+/// A corridor edge which could not be carved as requested.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CorridorFailure {
+    pub edge: (usize, usize),
+    pub recovery: Recovery,
+}
+
+/// Information about the corridor carving procedure.
+#[derive(Clone, Default, Debug)]
+pub struct CorridorReport {
+    /// The corridors in the order they were carved.
+    pub corridors: Vec<Corridor>,
+    /// The edges which could not be carved as requested in the order they were attempted.
+    pub failures: Vec<CorridorFailure>,
+}
+
+/// Returned when some of the rooms which the corridor edges connect could not be connected.
+#[derive(Debug)]
+pub struct CorridorError {
+    /// The grid with every corridor which could be carved.
+    pub grid: Grid,
+    pub report: CorridorReport,
+    /// Pairs of rooms which should have been connected but are not.
+    pub disconnected_rooms: Vec<(usize, usize)>,
+}
+
+impl std::fmt::Display for CorridorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} corridor(s) could not be carved, leaving {} pair(s) of rooms disconnected",
+            self.report
+                .failures
+                .iter()
+                .filter(|failure| failure.recovery == Recovery::Failed)
+                .count(),
+            self.disconnected_rooms.len()
+        )
+    }
+}
+
+impl std::error::Error for CorridorError {}
+
+/// Returned when a grid cannot be generated with a cost field.
+#[derive(Debug)]
+pub enum GridError {
+    /// The cost field is neither empty nor has a value for every tile.
+    CostFieldSize { expected: usize, found: usize },
+    /// Some of the rooms could not be connected.
+    Corridors(CorridorError),
+}
+
+impl std::fmt::Display for GridError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GridError::CostFieldSize { expected, found } => write!(
+                f,
+                "cost field has {found} values instead of {expected} (one for every tile)"
+            ),
+            GridError::Corridors(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for GridError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GridError::CostFieldSize { .. } => None,
+            GridError::Corridors(error) => Some(error),
+        }
+    }
+}
+
+impl From<CorridorError> for GridError {
+    fn from(error: CorridorError) -> Self {
+        GridError::Corridors(error)
+    }
+}
+
+/// A corridor carved in the grid.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Corridor {
+    /// Indices of the doorways which the corridor connects.
+    pub doorways: (usize, usize),
+    /// Indices of the tiles of the corridor in order from the first doorway to the second one,
+    /// including the doorways. For corridors wider than one tile these are the north-west tiles
+    /// of the squares the corridor is made of.
+    pub path: Vec<usize>,
+    /// Width of the corridor in tiles.
+    pub width: usize,
+    /// Number of times the corridor changes direction.
+    pub turns: usize,
+    /// Tiles of the path which had already been carved by earlier corridors.
+    pub shared_tiles: Vec<usize>,
+}
+
+impl Corridor {
+    /// The number of tiles of the corridor, including the doorways.
+    pub fn length(&self) -> usize {
+        self.path.len()
+    }
+}
+
+/// Counts the number of times a path changes direction.
+pub(crate) fn count_turns(path: &[usize]) -> usize {
+    path.windows(3)
+        .filter(|window| window[1].abs_diff(window[0]) != window[2].abs_diff(window[1]))
+        .count()
+}
+
+/// Widens a doorway to the corridor width along the side of its room, shifting it so that it
+/// does not go past the corner of the room. Returns the tiles of the widened doorway and the
+/// north-west tile of the square through which a corridor of that width leaves the doorway.
+fn widen_doorway(
+    dungeon: &Dungeon,
+    doorway_index: usize,
+    corridor_width: usize,
+    width: usize,
+) -> (Vec<usize>, usize) {
+    let doorway = dungeon.doorways[doorway_index];
+    let position = vec::to_index(doorway.position, width);
+    let Some(room) = dungeon
+        .rooms
+        .get(doorway.room_index)
+        .filter(|_| corridor_width > 1)
+    else {
+        return (vec![position], position);
+    };
+
+    let bounds = room.bounds;
+    let x = doorway.position.x as usize;
+    let y = doorway.position.y as usize;
+    let offset = corridor_width - 1;
+    if y + 1 == bounds.y || y == bounds.y + bounds.height {
+        // North or south side.
+        let room_end = bounds.x + bounds.width;
+        let start = x.min(room_end.saturating_sub(corridor_width)).max(bounds.x);
+        let doorway_tiles = (start..(start + corridor_width).min(room_end))
+            .map(|x| x + y * width)
+            .collect();
+        let anchor_y = if y + 1 == bounds.y { y - offset } else { y };
+        (doorway_tiles, start + anchor_y * width)
+    } else {
+        // West or east side.
+        let room_end = bounds.y + bounds.height;
+        let start = y.min(room_end.saturating_sub(corridor_width)).max(bounds.y);
+        let doorway_tiles = (start..(start + corridor_width).min(room_end))
+            .map(|y| x + y * width)
+            .collect();
+        let anchor_x = if x + 1 == bounds.x { x - offset } else { x };
+        (doorway_tiles, anchor_x + start * width)
+    }
+}
+
+/// Places a corridor wider than one tile in the grid by carving a square of tiles for every tile
+/// of the path. The corridor is surrounded by marker tiles so that narrower corridors do not run
+/// alongside it.
+fn place_wide_corridor(corridor_width: usize, width: usize, tiles: &mut [Tile], path: &[usize]) {
+    use Tile::*;
+
+    let square = |anchor: usize| {
+        (0..corridor_width)
+            .flat_map(move |row| (0..corridor_width).map(move |column| (row, column)))
+            .map(move |(row, column)| anchor + row * width + column)
+    };
+
+    for &anchor in path {
+        for tile in square(anchor) {
+            if !matches!(tiles[tile], Doorway) {
+                tiles[tile] = Corridor;
+            }
+        }
+    }
+
+    for &anchor in path {
+        for tile in square(anchor) {
+            for neighbor in [tile + 1, tile - 1, tile + width, tile - width] {
+                if matches!(tiles[neighbor], Wall) {
+                    tiles[neighbor] = CorridorNeighbor;
+                } else if matches!(tiles[neighbor], Room) {
+                    // Place a doorway marker tile inside the room to help the maze generation
+                    // procedure.
+                    tiles[neighbor] = Doorway;
+                }
+            }
+        }
+    }
+}
+
+/// Carves a single corridor of the given width between two doorways. If the pathfinding algorithm
+/// fails or the corridor breaks one of the shape limits, the tiles of the doorways are restored
+/// and the broken limit (if any) is returned.
+fn try_place_corridor(
+    configuration: &Configuration,
+    dungeon: &Dungeon,
+    edge: (usize, usize),
+    corridor_width: usize,
+    width: usize,
+    tiles: &mut [Tile],
+    pathfinder: &mut Pathfinder,
+) -> Result<Corridor, Option<ShapeLimit>> {
+    let (doorway_a, anchor_a) = widen_doorway(dungeon, edge.0, corridor_width, width);
+    let (doorway_b, anchor_b) = widen_doorway(dungeon, edge.1, corridor_width, width);
+    // Place doorways (which replace blocking tiles around the rooms) and create corridors.
+    let previous_tiles: Vec<(usize, Tile)> = doorway_a
+        .iter()
+        .chain(&doorway_b)
+        .map(|&tile| (tile, tiles[tile]))
+        .collect();
+    for &(tile, _) in &previous_tiles {
+        tiles[tile] = Tile::Doorway;
+    }
+    let path = pathfinder.find_wide_path(
+        configuration,
+        anchor_a,
+        anchor_b,
+        width,
+        tiles,
+        corridor_width,
+    );
+    // Diagonal steps get a corner tile, so that the corridor can be walked with orthogonal steps.
+    // A path whose corners would make a square is rejected like a missing one.
+    let path = match path {
+        [] => vec![],
+        path if configuration.diagonal_corridors && corridor_width == 1 => {
+            orthogonal_path(configuration.disallow_corridor_squares, width, tiles, path)
+                .unwrap_or_default()
+        }
+        path => path.to_vec(),
+    };
+    let turns = count_turns(&path);
+    let distance = (anchor_a % width).abs_diff(anchor_b % width)
+        + (anchor_a / width).abs_diff(anchor_b / width);
+    let broken_limit = if path.is_empty() {
+        None
+    } else if configuration
+        .max_corridor_turns
+        .is_some_and(|max_turns| turns > max_turns)
+    {
+        Some(ShapeLimit::Turns)
+    } else if configuration
+        .max_corridor_stretch
+        .is_some_and(|max_stretch| (path.len() - 1) * 100 > max_stretch * distance)
+    {
+        Some(ShapeLimit::Length)
+    } else {
+        None
+    };
+    if path.is_empty() || broken_limit.is_some() {
+        // Restore in reverse in case the two doorways overlap.
+        for &(tile, previous_tile) in previous_tiles.iter().rev() {
+            tiles[tile] = previous_tile;
+        }
+        return Err(broken_limit);
+    }
+
+    // The path goes from the end to the start.
+    let corridor = Corridor {
+        doorways: edge,
+        path: path.iter().rev().copied().collect(),
+        width: corridor_width,
+        turns,
+        shared_tiles: path
+            .iter()
+            .rev()
+            .copied()
+            .filter(|&tile| tiles[tile] == Tile::Corridor)
+            .collect(),
+    };
+    if corridor_width == 1 {
+        place_corridor(configuration.disallow_corridor_squares, width, tiles, &path);
+    } else {
+        place_wide_corridor(corridor_width, width, tiles, &path);
+    }
+    Ok(corridor)
+}
+
+/// Carves the corridors one by one. When a corridor cannot be carved, the procedure first retries
+/// with relaxed rules and then tries to connect the same two rooms through their other doorways,
+/// closest pairs first. Corridors which break the shape limits in every attempt are carved at the
+/// end without the limits, but only if their rooms are not connected through other corridors.
+/// Returns a report of the carved corridors and the corridors which could not be carved as
+/// requested.
 fn try_place_corridors(
     configuration: &Configuration,
     dungeon: &Dungeon,
     corridors: &Edges,
     width: usize,
     tiles: &mut [Tile],
-    open_set: &mut Heap<usize, usize>,
-    g_scores: &mut Vec<usize>,
-    parent: &mut Vec<usize>,
-    path: &mut Vec<usize>,
-) -> bool {
-    use Tile::*;
-    for edge in corridors {
-        let position_a = vec::to_index(dungeon.doorways[edge.0].position, width);
-        let position_b = vec::to_index(dungeon.doorways[edge.1].position, width);
-        // Place doorways (which replace blocking tiles around the rooms) and create corridors.
-        tiles[position_a] = Doorway;
-        tiles[position_b] = Doorway;
-        a_star::a_star(
-            configuration,
-            position_a,
-            position_b,
-            width,
-            tiles,
-            open_set,
-            g_scores,
-            parent,
-            path,
-        );
-        if path.is_empty() {
-            return false;
+    pathfinder: &mut Pathfinder,
+) -> CorridorReport {
+    let mut report = CorridorReport::default();
+
+    let default_configuration = Configuration::default();
+    let relaxed_configuration = Configuration {
+        disallow_corridor_squares: false,
+        corridor_cost: default_configuration.corridor_cost,
+        straight_cost: default_configuration.straight_cost,
+        standard_cost: default_configuration.standard_cost,
+        ..configuration.clone()
+    };
+    let unlimited_configuration = Configuration {
+        max_corridor_turns: None,
+        max_corridor_stretch: None,
+        ..configuration.clone()
+    };
+    let unlimited_relaxed_configuration = Configuration {
+        max_corridor_turns: None,
+        max_corridor_stretch: None,
+        ..relaxed_configuration.clone()
+    };
+
+    let mut room_doorways: Vec<Vec<usize>> = vec![];
+    for (doorway_index, doorway) in dungeon.doorways.iter().enumerate() {
+        if room_doorways.len() <= doorway.room_index {
+            room_doorways.resize(doorway.room_index + 1, vec![]);
         }
-        place_corridor(configuration.disallow_corridor_squares, width, tiles, path);
+        room_doorways[doorway.room_index].push(doorway_index);
     }
-    true
+    let room_of = |doorway: usize| dungeon.doorways[doorway].room_index;
+
+    // Corridors which connect two rooms for the first time are the main ones, the rest only make
+    // loops (e.g. the reintroduced ones).
+    let mut connected_rooms = DisjointSet::new(room_doorways.len());
+    let corridor_widths: Vec<usize> = corridors
+        .iter()
+        .map(|edge| {
+            let room_a = connected_rooms.find_set(room_of(edge.0));
+            let room_b = connected_rooms.find_set(room_of(edge.1));
+            if room_a == room_b {
+                configuration
+                    .reintroduced_corridor_width
+                    .unwrap_or(configuration.corridor_width)
+            } else {
+                connected_rooms.union_sets(room_a, room_b);
+                configuration.corridor_width
+            }
+        })
+        .collect();
+
+    // Tries to carve the edge with the first configuration, then with the second (relaxed) one and
+    // then through the other pairs of doorways of the two rooms, the closest ones first. Returns
+    // the corridor with the recovery it needed or the first limit it broke.
+    let mut carve = |edge_configurations: [&Configuration; 2],
+                     edge: (usize, usize),
+                     corridor_width: usize|
+     -> Result<(Corridor, Option<Recovery>), Option<ShapeLimit>> {
+        let mut broken_limit: Option<ShapeLimit> = None;
+        for (edge_configuration, recovery) in edge_configurations
+            .into_iter()
+            .zip([None, Some(Recovery::Relaxed)])
+        {
+            match try_place_corridor(
+                edge_configuration,
+                dungeon,
+                edge,
+                corridor_width,
+                width,
+                tiles,
+                pathfinder,
+            ) {
+                Ok(corridor) => return Ok((corridor, recovery)),
+                Err(limit) => broken_limit = broken_limit.or(limit),
+            }
+        }
+
+        let mut alternatives: Edges = vec![];
+        for &doorway_a in &room_doorways[room_of(edge.0)] {
+            for &doorway_b in &room_doorways[room_of(edge.1)] {
+                if (doorway_a, doorway_b) != edge {
+                    alternatives.push((doorway_a, doorway_b));
+                }
+            }
+        }
+        alternatives.sort_by_key(|alternative| {
+            let distance =
+                dungeon.doorways[alternative.0].position - dungeon.doorways[alternative.1].position;
+            distance.x.unsigned_abs() + distance.y.unsigned_abs()
+        });
+
+        for (edge_configuration, relaxed) in edge_configurations.into_iter().zip([false, true]) {
+            for &alternative in &alternatives {
+                match try_place_corridor(
+                    edge_configuration,
+                    dungeon,
+                    alternative,
+                    corridor_width,
+                    width,
+                    tiles,
+                    pathfinder,
+                ) {
+                    Ok(corridor) => {
+                        let recovery = Recovery::Rerouted {
+                            edge: alternative,
+                            relaxed,
+                        };
+                        return Ok((corridor, Some(recovery)));
+                    }
+                    Err(limit) => broken_limit = broken_limit.or(limit),
+                }
+            }
+        }
+
+        Err(broken_limit)
+    };
+
+    let mut limited_edges: Vec<((usize, usize), usize, ShapeLimit)> = vec![];
+    for (&edge, &corridor_width) in corridors.iter().zip(&corridor_widths) {
+        match carve(
+            [configuration, &relaxed_configuration],
+            edge,
+            corridor_width,
+        ) {
+            Ok((corridor, recovery)) => {
+                report.corridors.push(corridor);
+                if let Some(recovery) = recovery {
+                    report.failures.push(CorridorFailure { edge, recovery });
+                }
+            }
+            Err(Some(limit)) => limited_edges.push((edge, corridor_width, limit)),
+            Err(None) => report.failures.push(CorridorFailure {
+                edge,
+                recovery: Recovery::Failed,
+            }),
+        }
+    }
+
+    // The corridors which broke the limits are needed only if their rooms are not connected yet.
+    let mut carved_rooms = DisjointSet::new(room_doorways.len());
+    for corridor in &report.corridors {
+        carved_rooms.union_sets(room_of(corridor.doorways.0), room_of(corridor.doorways.1));
+    }
+    for (edge, corridor_width, limit) in limited_edges {
+        if carved_rooms.find_set(room_of(edge.0)) == carved_rooms.find_set(room_of(edge.1)) {
+            report.failures.push(CorridorFailure {
+                edge,
+                recovery: Recovery::Dropped { limit },
+            });
+            continue;
+        }
+        let recovery = match carve(
+            [&unlimited_configuration, &unlimited_relaxed_configuration],
+            edge,
+            corridor_width,
+        ) {
+            Ok((corridor, _)) => {
+                carved_rooms.union_sets(room_of(edge.0), room_of(edge.1));
+                report.corridors.push(corridor);
+                Recovery::Unlimited { limit }
+            }
+            Err(_) => Recovery::Failed,
+        };
+        report.failures.push(CorridorFailure { edge, recovery });
+    }
+
+    report
 }
 
-/// Takes a room graph and creates the corresponding grid given the options in the configuration
-/// structure. Uses the A* algorithm to carve corridors between the rooms, while ensuring that no
-/// corridors make a 2x2 square (aesthetic choice).
-///
-/// If the corridor generation fails (which can happen if the configuration values for the
-/// different costs are more extreme) it regenerates the corridor with the default values.
-pub fn make_grid(
-    configuration: &Configuration,
-    grid_dimensions: Vector2,
+/// Finds the pairs of rooms which the corridor edges should connect, but which are not connected
+/// by the corridors which were carved successfully.
+pub(crate) fn disconnected_rooms(
     dungeon: &Dungeon,
     corridors: &Edges,
-) -> Grid {
+    report: &CorridorReport,
+) -> Vec<(usize, usize)> {
+    let room_of = |doorway: usize| dungeon.doorways[doorway].room_index;
+    let room_count = dungeon
+        .doorways
+        .iter()
+        .map(|doorway| doorway.room_index + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut disjoint_set = DisjointSet::new(room_count);
+    for corridor in &report.corridors {
+        disjoint_set.union_sets(room_of(corridor.doorways.0), room_of(corridor.doorways.1));
+    }
+
+    let mut result: Vec<(usize, usize)> = vec![];
+    for &edge in corridors {
+        let rooms = (room_of(edge.0), room_of(edge.1));
+        if disjoint_set.find_set(rooms.0) != disjoint_set.find_set(rooms.1)
+            && !result.contains(&rooms)
+        {
+            result.push(rooms);
+        }
+    }
+    result
+}
+
+/// Creates a grid of walls with a perimeter of blockers and carves the rooms, each surrounded by
+/// blockers, into it.
+pub(crate) fn carve_rooms(grid_dimensions: Vector2, dungeon: &Dungeon) -> Vec<Tile> {
     use Tile::*;
 
     let grid_width = grid_dimensions.x as usize;
@@ -226,43 +1013,88 @@ pub fn make_grid(
         }
     }
 
-    let mut open_set: Heap<usize, usize> = Heap::with_capacity(tiles.len() / 4);
-    let mut g_scores: Vec<usize> = vec![];
-    let mut parent: Vec<usize> = vec![];
-    let mut path: Vec<usize> = vec![];
+    tiles
+}
 
-    let mut tiles_clone = tiles.clone();
-    if try_place_corridors(
+/// Takes a room graph and creates the corresponding grid given the options in the configuration
+/// structure. Uses the A* algorithm to carve corridors between the rooms, while ensuring that no
+/// corridors make a 2x2 square (aesthetic choice).
+///
+/// If a corridor cannot be carved (which can happen if the configuration values for the different
+/// costs are more extreme) it is carved with relaxed rules or through a different pair of doorways
+/// of the same rooms. Every such corridor is listed in the report. If some rooms which the edges
+/// connect remain disconnected, an error with the incomplete grid is returned instead. The report
+/// also lists the paths of all carved corridors.
+pub fn make_grid(
+    configuration: &Configuration,
+    grid_dimensions: Vector2,
+    dungeon: &Dungeon,
+    corridors: &Edges,
+) -> Result<(Grid, CorridorReport), CorridorError> {
+    carve_grid(configuration, grid_dimensions, dungeon, corridors, &[])
+}
+
+/// Same as make_grid, but the A* algorithm adds the given cost of each tile to the cost of
+/// entering it, which lets the corridors avoid some areas and prefer others. The cost field has a
+/// value for every tile (row by row) or is empty, otherwise an error is returned.
+pub fn make_grid_with_costs(
+    configuration: &Configuration,
+    grid_dimensions: Vector2,
+    dungeon: &Dungeon,
+    corridors: &Edges,
+    cost_field: &[usize],
+) -> Result<(Grid, CorridorReport), GridError> {
+    let tile_count = grid_dimensions.x as usize * grid_dimensions.y as usize;
+    if !cost_field.is_empty() && cost_field.len() != tile_count {
+        return Err(GridError::CostFieldSize {
+            expected: tile_count,
+            found: cost_field.len(),
+        });
+    }
+    Ok(carve_grid(
+        configuration,
+        grid_dimensions,
+        dungeon,
+        corridors,
+        cost_field,
+    )?)
+}
+
+/// Carves the rooms and the corridors with the given cost field, which has the right size.
+fn carve_grid(
+    configuration: &Configuration,
+    grid_dimensions: Vector2,
+    dungeon: &Dungeon,
+    corridors: &Edges,
+    cost_field: &[usize],
+) -> Result<(Grid, CorridorReport), CorridorError> {
+    let grid_width = grid_dimensions.x as usize;
+    let mut tiles = carve_rooms(grid_dimensions, dungeon);
+
+    let mut pathfinder = Pathfinder::new(tiles.len());
+    pathfinder.set_cost_field(cost_field);
+    let report = try_place_corridors(
         configuration,
         dungeon,
         corridors,
         grid_width,
-        &mut tiles_clone,
-        &mut open_set,
-        &mut g_scores,
-        &mut parent,
-        &mut path,
-    ) {
-        Grid {
-            width: grid_width,
-            tiles: tiles_clone,
-        }
+        &mut tiles,
+        &mut pathfinder,
+    );
+
+    let grid = Grid {
+        width: grid_width,
+        tiles,
+    };
+    let disconnected_rooms = disconnected_rooms(dungeon, corridors, &report);
+    if disconnected_rooms.is_empty() {
+        Ok((grid, report))
     } else {
-        try_place_corridors(
-            &Default::default(),
-            dungeon,
-            corridors,
-            grid_width,
-            &mut tiles,
-            &mut open_set,
-            &mut g_scores,
-            &mut parent,
-            &mut path,
-        );
-        Grid {
-            width: grid_width,
-            tiles,
-        }
+        Err(CorridorError {
+            grid,
+            report,
+            disconnected_rooms,
+        })
     }
 }
 
@@ -272,6 +1104,7 @@ mod test {
     use crate::{
         mock::{doorway, room},
         vec::{to_index, vec2u},
+        verify::verify,
     };
 
     #[test]
@@ -319,7 +1152,7 @@ mod test {
             "Grid does not display properly."
         );
 
-        let result: Grid = string.into();
+        let result: Grid = string.parse().expect("Grid should be valid.");
         assert!(
             grid.width == result.width && grid.tiles.len() == result.tiles.len(),
             "Parsed grid does not match size."
@@ -330,13 +1163,187 @@ mod test {
         );
     }
 
+    #[test]
+    fn grid_parsing_errors() {
+        let cases = [
+            ("", GridParseError::Empty),
+            ("\n\n", GridParseError::Empty),
+            (
+                "%%%\n%x%\n",
+                GridParseError::UnknownCharacter {
+                    line: 2,
+                    column: 2,
+                    character: 'x',
+                },
+            ),
+            (
+                "%%%\n%_%\n%%\n",
+                GridParseError::InconsistentLength {
+                    line: 3,
+                    expected: 3,
+                    found: 2,
+                },
+            ),
+        ];
+        for (string, expected) in cases {
+            assert_eq!(
+                Grid::try_from(string).expect_err("Grid should be invalid."),
+                expected,
+                "Wrong error for {string:?}."
+            );
+        }
+        assert_eq!(
+            GridParseError::UnknownCharacter {
+                line: 2,
+                column: 2,
+                character: 'x',
+            }
+            .to_string(),
+            "unknown tile 'x' at line 2, column 2",
+            "Error message is incorrect."
+        );
+
+        // The lenient mode never fails.
+        let grid = Grid::parse_lenient("%x%\n%_\n");
+        assert_eq!(
+            grid.to_string(),
+            "%.%\n%_.\n",
+            "Unknown characters and missing tiles should be empty."
+        );
+        let grid = Grid::parse_lenient("");
+        assert!(grid.tiles.is_empty(), "Empty string should give no tiles.");
+        assert_eq!(
+            grid.to_string(),
+            "",
+            "Empty grid should display as nothing."
+        );
+    }
+
+    #[test]
+    fn coordinate_api() {
+        let mut grid = Grid::parse_lenient(
+            "\
+            %%%%\n\
+            %_d%\n\
+            %%%%\n",
+        );
+        assert_eq!(grid.height(), 3, "Height is incorrect.");
+        assert_eq!(grid.to_index(2, 1), Some(6), "Index is incorrect.");
+        assert_eq!(grid.to_coordinates(6), (2, 1), "Coordinates are incorrect.");
+        assert_eq!(grid[(2, 1)], Tile::Doorway, "Indexing is incorrect.");
+        // The index of the tiles would be valid, but the coordinates are not.
+        assert_eq!(
+            grid.get(4, 0),
+            None,
+            "Tiles outside the grid should not exist."
+        );
+        assert_eq!(
+            grid.get(0, 3),
+            None,
+            "Tiles outside the grid should not exist."
+        );
+        *grid.get_mut(1, 1).expect("Tile should exist.") = Tile::Wall;
+        grid[(2, 1)] = Tile::Room;
+        assert_eq!(
+            grid.to_string(),
+            "%%%%\n%#_%\n%%%%\n",
+            "Tiles should be modified."
+        );
+
+        let neighbors: Vec<(usize, usize)> = grid.neighbors4(0, 0).collect();
+        assert_eq!(neighbors, vec![(1, 0), (0, 1)], "Corner has two neighbors.");
+        assert_eq!(
+            grid.neighbors4(1, 1).count(),
+            4,
+            "Inner tile has four neighbors."
+        );
+        assert_eq!(
+            grid.neighbors8(0, 1).count(),
+            5,
+            "Side tile has five neighbors."
+        );
+        assert_eq!(
+            grid.neighbors8(1, 1).count(),
+            8,
+            "Inner tile has eight neighbors."
+        );
+
+        let rows: Vec<String> = grid
+            .rows()
+            .map(|row| row.iter().map(|&tile| char::from(tile)).collect())
+            .collect();
+        assert_eq!(rows, vec!["%%%%", "%#_%", "%%%%"], "Rows are incorrect.");
+        for row in grid.rows_mut() {
+            row[0] = Tile::Empty;
+        }
+        assert_eq!(grid[(0, 2)], Tile::Empty, "Rows should be modified.");
+
+        // The region is clipped to the grid.
+        let region: Vec<((usize, usize), Tile)> =
+            grid.region_tiles(Rectangle::new(2, 1, 5, 5)).collect();
+        assert_eq!(
+            region,
+            vec![
+                ((2, 1), Tile::Room),
+                ((3, 1), Tile::Blocker),
+                ((2, 2), Tile::Blocker),
+                ((3, 2), Tile::Blocker),
+            ],
+            "Region is incorrect."
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn indexing_outside_grid() {
+        let grid = Grid::parse_lenient("%%\n%%\n");
+        // Would be the first tile of the second row with an unchecked index.
+        let _ = grid[(2, 0)];
+    }
+
+    #[test]
+    fn cost_field_of_wrong_size() {
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![],
+        };
+        let result = make_grid_with_costs(
+            &Configuration::default(),
+            vec2u(4, 4),
+            &dungeon,
+            &vec![],
+            &[1; 15],
+        );
+        assert!(
+            matches!(
+                result,
+                Err(GridError::CostFieldSize {
+                    expected: 16,
+                    found: 15
+                })
+            ),
+            "A cost field of the wrong size should be rejected: {result:?}"
+        );
+        assert!(
+            make_grid_with_costs(
+                &Configuration::default(),
+                vec2u(4, 4),
+                &dungeon,
+                &vec![],
+                &[1; 16],
+            )
+            .is_ok(),
+            "A cost field with a value for every tile should be accepted."
+        );
+    }
+
     #[test]
     fn corridor_placement() {
         // This grid tests that the place_corridor procedure does not override blockers, rooms and
         // doorways and already placed corridors. It tests whether the corridor neighbors are
         // placed appropriatelly and whether blockers are placed in spots where we are certain a
         // corridor should not be placed.
-        let Grid { width, mut tiles } = Grid::from(
+        let Grid { width, mut tiles } = Grid::parse_lenient(
             "\
             %%%%%%%%%%%%%\n\
             %_%##@c@####%\n\
@@ -362,7 +1369,7 @@ mod test {
 
         place_corridor(true, width, &mut tiles, path);
 
-        let correct_grid = Grid::from(
+        let correct_grid = Grid::parse_lenient(
             "\
             %%%%%%%%%%%%%\n\
             %_%@#@c@####%\n\
@@ -408,8 +1415,21 @@ mod test {
         let grid_dimension = configuration.min_padding * 3 + configuration.min_room_dimension * 2;
         let grid_dimensions = vec2u(grid_dimension, grid_dimension);
 
-        let grid = make_grid(&configuration, grid_dimensions, &dungeon, &corridors);
-        let correct_grid = Grid::from(
+        let (grid, report) = make_grid(&configuration, grid_dimensions, &dungeon, &corridors)
+            .expect("All corridors should be carved.");
+        assert!(report.failures.is_empty(), "No corridor should fail.");
+        assert_eq!(
+            report.corridors.len(),
+            corridors.len(),
+            "Every corridor should be in the report."
+        );
+        for corridor in &report.corridors {
+            assert!(
+                corridor.length() == 3 && corridor.turns == 0 && corridor.shared_tiles.is_empty(),
+                "Every corridor should be a straight line of three tiles."
+            );
+        }
+        let correct_grid = Grid::parse_lenient(
             "\
             %%%%%%%%%%%%%%%%%%%\n\
             %#################%\n\
@@ -438,4 +1458,410 @@ mod test {
             "Grids should match contents."
         );
     }
+
+    #[test]
+    fn corridor_rerouting() {
+        // The first doorway is in the middle of the room so it cannot be reached. The corridor
+        // should be rerouted through the other doorway of the same room.
+        let configuration = Configuration::default();
+        let dungeon = Dungeon {
+            rooms: vec![room(3, 3, 5, 5), room(11, 3, 5, 5)],
+            doorways: vec![doorway(5, 5, 0), doorway(8, 5, 0), doorway(10, 5, 1)],
+        };
+        let corridors = vec![(0, 2)];
+
+        let (grid, report) = make_grid(&configuration, vec2u(19, 19), &dungeon, &corridors)
+            .expect("The rooms should be connected.");
+        assert_eq!(
+            &report.failures,
+            &[CorridorFailure {
+                edge: (0, 2),
+                recovery: Recovery::Rerouted {
+                    edge: (1, 2),
+                    relaxed: false
+                },
+            }],
+            "The corridor should be rerouted through the second doorway."
+        );
+        assert_eq!(
+            grid.tiles[to_index(vec2u(5, 5), grid.width)],
+            Tile::Room,
+            "The doorway of the failed corridor should be removed."
+        );
+        assert_eq!(
+            grid.tiles[to_index(vec2u(9, 5), grid.width)],
+            Tile::Corridor,
+            "The rerouted corridor should be carved."
+        );
+    }
+
+    #[test]
+    fn corridor_failure() {
+        let configuration = Configuration::default();
+        let dungeon = Dungeon {
+            rooms: vec![room(3, 3, 5, 5), room(11, 3, 5, 5)],
+            doorways: vec![doorway(5, 5, 0), doorway(10, 5, 1)],
+        };
+        let corridors = vec![(0, 1)];
+
+        let error = make_grid(&configuration, vec2u(19, 19), &dungeon, &corridors)
+            .expect_err("The rooms cannot be connected.");
+        assert_eq!(
+            &error.report.failures,
+            &[CorridorFailure {
+                edge: (0, 1),
+                recovery: Recovery::Failed,
+            }],
+            "The corridor should be reported as failed."
+        );
+        assert_eq!(
+            &error.disconnected_rooms,
+            &[(0, 1)],
+            "The two rooms should be reported as disconnected."
+        );
+    }
+
+    #[test]
+    fn connectivity_through_other_corridors() {
+        // The direct corridor between the first two rooms failed, but they are connected through
+        // the third room.
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![doorway(0, 0, 0), doorway(0, 0, 1), doorway(0, 0, 2)],
+        };
+        let corridors = vec![(0, 1), (1, 2), (0, 2)];
+        let carved = |edge: (usize, usize)| Corridor {
+            doorways: edge,
+            path: vec![],
+            width: 1,
+            turns: 0,
+            shared_tiles: vec![],
+        };
+        let report = CorridorReport {
+            corridors: vec![carved((1, 2)), carved((0, 2))],
+            failures: vec![CorridorFailure {
+                edge: (0, 1),
+                recovery: Recovery::Failed,
+            }],
+        };
+        assert!(
+            disconnected_rooms(&dungeon, &corridors, &report).is_empty(),
+            "The rooms should be connected."
+        );
+
+        let report = CorridorReport {
+            corridors: vec![carved((1, 2))],
+            failures: vec![
+                CorridorFailure {
+                    edge: (0, 1),
+                    recovery: Recovery::Failed,
+                },
+                CorridorFailure {
+                    edge: (0, 2),
+                    recovery: Recovery::Failed,
+                },
+            ],
+        };
+        assert_eq!(
+            &disconnected_rooms(&dungeon, &corridors, &report),
+            &[(0, 1), (0, 2)],
+            "The first room should be disconnected from the others."
+        );
+    }
+
+    #[test]
+    fn corridor_paths() {
+        // The corridor is cheaper to go through the already placed corridor.
+        let configuration = Configuration {
+            straight_cost: 9,
+            standard_cost: 10,
+            ..Default::default()
+        };
+        let Grid { width, mut tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %#########%\n\
+            %#########%\n\
+            %d#######d%\n\
+            %##@@@@@##%\n\
+            %#@ccccc@#%\n\
+            %%%%%%%%%%%\n",
+        );
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![doorway(1, 3, 0), doorway(9, 3, 1)],
+        };
+
+        let report = try_place_corridors(
+            &configuration,
+            &dungeon,
+            &vec![(0, 1)],
+            width,
+            &mut tiles,
+            &mut Pathfinder::new(0),
+        );
+
+        let corridor = &report.corridors[0];
+        assert_eq!(corridor.doorways, (0, 1), "Doorways are incorrect.");
+        assert_eq!(corridor.length(), 13, "Length is incorrect.");
+        assert_eq!(
+            corridor.path[0],
+            to_index(vec2u(1, 3), width),
+            "The path should start at the first doorway."
+        );
+        assert_eq!(
+            corridor.path[12],
+            to_index(vec2u(9, 3), width),
+            "The path should end at the second doorway."
+        );
+        assert_eq!(corridor.turns, 3, "Turn count is incorrect.");
+        assert_eq!(
+            &corridor.shared_tiles,
+            &(3..=7)
+                .map(|x| to_index(vec2u(x, 5), width))
+                .collect::<Vec<_>>(),
+            "Shared tiles are incorrect."
+        );
+    }
+
+    #[test]
+    fn diagonal_corridor_paths() {
+        // Four of the steps are diagonal, each of which needs a corner tile.
+        let configuration = Configuration {
+            diagonal_corridors: true,
+            ..Default::default()
+        };
+        let empty = "\
+            %%%%%%%%%%%\n\
+            %d########%\n\
+            %#########%\n\
+            %#########%\n\
+            %#########%\n\
+            %########d%\n\
+            %%%%%%%%%%%\n";
+        let Grid { width, mut tiles } = Grid::parse_lenient(empty);
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![doorway(1, 1, 0), doorway(9, 5, 1)],
+        };
+
+        let report = try_place_corridors(
+            &configuration,
+            &dungeon,
+            &vec![(0, 1)],
+            width,
+            &mut tiles,
+            &mut Pathfinder::new(0),
+        );
+
+        let corridor = &report.corridors[0];
+        assert_eq!(corridor.length(), 13, "Length is incorrect.");
+        assert!(
+            corridor
+                .path
+                .windows(2)
+                .all(|step| step[0].abs_diff(step[1]) == 1 || step[0].abs_diff(step[1]) == width),
+            "Every step of the corridor should be orthogonal."
+        );
+        assert!(
+            corridor
+                .path
+                .iter()
+                .all(|&tile| matches!(tiles[tile], Tile::Corridor | Tile::Doorway)),
+            "Every tile of the path should be carved."
+        );
+        let verification = verify(&Grid { width, tiles }, &dungeon);
+        assert!(
+            verification.corridor_squares.is_empty(),
+            "The corner tiles should not make squares."
+        );
+
+        // The path has a single turn, but the corners make a staircase with many more.
+        let limited_configuration = Configuration {
+            max_corridor_turns: Some(1),
+            ..configuration
+        };
+        let Grid { width, mut tiles } = Grid::parse_lenient(empty);
+        let report = try_place_corridors(
+            &limited_configuration,
+            &dungeon,
+            &vec![(0, 1)],
+            width,
+            &mut tiles,
+            &mut Pathfinder::new(0),
+        );
+        assert_eq!(
+            report.corridors[0].turns,
+            count_turns(&report.corridors[0].path),
+            "The turns should be counted on the carved path."
+        );
+        assert_eq!(
+            report.failures[0].recovery,
+            Recovery::Unlimited {
+                limit: ShapeLimit::Turns
+            },
+            "The carved path breaks the turn limit."
+        );
+    }
+
+    #[test]
+    fn diagonal_corners_making_squares() {
+        // Both corners of the diagonal step from (2, 2) to (3, 3) complete a square of corridors.
+        let Grid { width, tiles } = Grid::parse_lenient(
+            "\
+            %%%%%%\n\
+            %#cc#%\n\
+            %c###%\n\
+            %c###%\n\
+            %####%\n\
+            %%%%%%\n",
+        );
+        let path = [to_index(vec2u(2, 2), width), to_index(vec2u(3, 3), width)];
+        assert_eq!(
+            orthogonal_path(true, width, &tiles, &path),
+            None,
+            "The path should be rejected when squares are disallowed."
+        );
+        assert_eq!(
+            orthogonal_path(false, width, &tiles, &path),
+            Some(vec![path[0], to_index(vec2u(3, 2), width), path[1]]),
+            "The first corner should be used when squares are allowed."
+        );
+    }
+
+    #[test]
+    fn wide_corridors() {
+        // The first corridor connects the rooms and the second one only makes a loop.
+        let configuration = Configuration {
+            corridor_width: 2,
+            reintroduced_corridor_width: Some(1),
+            ..Default::default()
+        };
+        let dungeon = Dungeon {
+            rooms: vec![room(2, 2, 5, 5), room(12, 2, 5, 5)],
+            doorways: vec![
+                doorway(7, 3, 0),
+                doorway(7, 6, 0),
+                doorway(11, 3, 1),
+                doorway(11, 6, 1),
+            ],
+        };
+        let (grid, report) = make_grid(
+            &configuration,
+            vec2u(20, 9),
+            &dungeon,
+            &vec![(0, 2), (1, 3)],
+        )
+        .expect("The corridors should be carved.");
+        assert_eq!(
+            grid.to_string(),
+            "\
+            %%%%%%%%%%%%%%%%%%%%\n\
+            %%%%%%%%###%%%%%%%#%\n\
+            %%_____%@@@%_____%#%\n\
+            %%____ddcccdd____%#%\n\
+            %%____ddcccdd____%#%\n\
+            %%_____%%%%%_____%#%\n\
+            %%____ddcccdd____%#%\n\
+            %%%%%%%%@@@%%%%%%%#%\n\
+            %%%%%%%%%%%%%%%%%%%%\n",
+            "The doorways and the main corridor should be widened."
+        );
+        assert_eq!(
+            report
+                .corridors
+                .iter()
+                .map(|corridor| corridor.width)
+                .collect::<Vec<_>>(),
+            vec![2, 1],
+            "Corridor widths are incorrect."
+        );
+    }
+
+    #[test]
+    fn corridor_shape_limits() {
+        // The corridor between the two doorways on the left and the right of the wall has to make
+        // turns and be longer than the distance between them.
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %d#######d%\n\
+            %#########%\n\
+            %####%####%\n\
+            %d###%###d%\n\
+            %####%####%\n\
+            %%%%%%%%%%%\n",
+        );
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![
+                doorway(1, 1, 0),
+                doorway(9, 1, 1),
+                doorway(1, 4, 0),
+                doorway(9, 4, 2),
+            ],
+        };
+        let place = |configuration: &Configuration, corridors: &Edges| {
+            let mut tiles = grid.tiles.clone();
+            try_place_corridors(
+                configuration,
+                &dungeon,
+                corridors,
+                grid.width,
+                &mut tiles,
+                &mut Pathfinder::new(0),
+            )
+        };
+        let limited_turns = Configuration {
+            max_corridor_turns: Some(0),
+            ..Default::default()
+        };
+
+        // The second corridor cannot go straight down past the end of the first one, but it is
+        // needed to connect the third room. After that the last corridor is not needed anymore.
+        let report = place(&limited_turns, &vec![(0, 1), (1, 3), (2, 3)]);
+        assert_eq!(report.corridors.len(), 2, "Corridor count is incorrect.");
+        assert_eq!(
+            &report.failures,
+            &[
+                CorridorFailure {
+                    edge: (1, 3),
+                    recovery: Recovery::Unlimited {
+                        limit: ShapeLimit::Turns
+                    },
+                },
+                CorridorFailure {
+                    edge: (2, 3),
+                    recovery: Recovery::Dropped {
+                        limit: ShapeLimit::Turns
+                    },
+                },
+            ],
+            "Only the corridor which is needed should be carved."
+        );
+
+        // The first doorway can be connected to the third room by a corridor as long as the
+        // distance between them.
+        let limited_length = Configuration {
+            max_corridor_stretch: Some(100),
+            ..Default::default()
+        };
+        let report = place(&limited_length, &vec![(0, 1), (2, 3)]);
+        assert_eq!(
+            &report.failures,
+            &[CorridorFailure {
+                edge: (2, 3),
+                recovery: Recovery::Rerouted {
+                    edge: (0, 3),
+                    relaxed: false
+                },
+            }],
+            "The long corridor should be rerouted."
+        );
+        assert_eq!(
+            report.corridors[1].length(),
+            12,
+            "The rerouted corridor should be as long as the distance between its doorways."
+        );
+    }
 }
@@ -0,0 +1,342 @@
+use crate::Configuration;
+use crate::grid::{Grid, Tile};
+use crate::maze::has_interior;
+use crate::rng::Rng;
+use crate::room::{Dungeon, Room};
+
+/// Patterns of walls which decorate the inside of a room. None of them places walls on the tiles
+/// along the sides of the room (where the doorway markers are) except the split, which leaves the
+/// doorway markers open and has a door of its own, so every tile of the room stays reachable from
+/// every doorway.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InteriorFeature {
+    /// Single wall tiles on every other tile, a tile away from the sides.
+    Pillars,
+    /// A ring of walls a tile away from the sides with an opening on every side.
+    InnerRing,
+    /// A line of walls across the longer dimension of the room with a door.
+    Split,
+    /// A smaller room of random dimensions inside the room with a single door.
+    NestedRoom,
+}
+
+impl InteriorFeature {
+    pub const ALL: [InteriorFeature; 4] = [
+        InteriorFeature::Pillars,
+        InteriorFeature::InnerRing,
+        InteriorFeature::Split,
+        InteriorFeature::NestedRoom,
+    ];
+
+    /// Whether the feature fits in a room of the given dimensions.
+    pub fn fits(self, width: usize, height: usize) -> bool {
+        match self {
+            InteriorFeature::Pillars => width >= 3 && height >= 3,
+            InteriorFeature::Split => width.max(height) >= 5,
+            InteriorFeature::InnerRing | InteriorFeature::NestedRoom => width >= 5 && height >= 5,
+        }
+    }
+}
+
+/// Parameters of the interior decoration of the rooms. Like the mazes, the interiors are placed
+/// in a proportion of the rooms whose dimensions are within the limits.
+#[derive(Clone, Debug)]
+pub struct RoomInteriors {
+    /// What proportion of the rooms of suitable size get an interior.
+    pub chance: f32,
+    pub min_dimension: usize,
+    pub max_dimension: usize,
+    /// The features to pick from. Every room gets one of those which fit in it at random.
+    pub features: Vec<InteriorFeature>,
+}
+
+impl Default for RoomInteriors {
+    fn default() -> Self {
+        Self {
+            chance: 0.3,
+            min_dimension: 5,
+            max_dimension: 20,
+            features: InteriorFeature::ALL.to_vec(),
+        }
+    }
+}
+
+/// Marks the walls of the feature on an area of the given dimensions. The feature must fit in the
+/// area.
+fn feature_walls<R: Rng>(
+    feature: InteriorFeature,
+    width: usize,
+    height: usize,
+    rng: &mut R,
+) -> Vec<bool> {
+    let mut walls = vec![false; width * height];
+    match feature {
+        InteriorFeature::Pillars => {
+            for y in (1..height - 1).step_by(2) {
+                for x in (1..width - 1).step_by(2) {
+                    walls[x + y * width] = true;
+                }
+            }
+        }
+        InteriorFeature::InnerRing => {
+            let (right, bottom) = (width - 2, height - 2);
+            for x in 1..=right {
+                walls[x + width] = true;
+                walls[x + bottom * width] = true;
+            }
+            for y in 1..=bottom {
+                walls[1 + y * width] = true;
+                walls[right + y * width] = true;
+            }
+            // An opening on every side, away from the corners.
+            for y in [1, bottom] {
+                walls[rng.random_range(2..=right - 1) + y * width] = false;
+            }
+            for x in [1, right] {
+                walls[x + rng.random_range(2..=bottom - 1) * width] = false;
+            }
+        }
+        InteriorFeature::Split => {
+            if width >= height {
+                let x = rng.random_range(2..=width - 3);
+                for y in 0..height {
+                    walls[x + y * width] = true;
+                }
+                walls[x + rng.random_range(0..=height - 1) * width] = false;
+            } else {
+                let y = rng.random_range(2..=height - 3);
+                for x in 0..width {
+                    walls[x + y * width] = true;
+                }
+                walls[rng.random_range(0..=width - 1) + y * width] = false;
+            }
+        }
+        InteriorFeature::NestedRoom => {
+            // The nested room keeps a tile of space from the sides.
+            let (inner_width, inner_height) = (
+                rng.random_range(3..=width - 2),
+                rng.random_range(3..=height - 2),
+            );
+            let left = rng.random_range(1..=width - 1 - inner_width);
+            let top = rng.random_range(1..=height - 1 - inner_height);
+            let (right, bottom) = (left + inner_width - 1, top + inner_height - 1);
+            for x in left..=right {
+                walls[x + top * width] = true;
+                walls[x + bottom * width] = true;
+            }
+            for y in top..=bottom {
+                walls[left + y * width] = true;
+                walls[right + y * width] = true;
+            }
+            let door = match rng.random_range(0..=3) {
+                0 => rng.random_range(left + 1..=right - 1) + top * width,
+                1 => rng.random_range(left + 1..=right - 1) + bottom * width,
+                2 => left + rng.random_range(top + 1..=bottom - 1) * width,
+                _ => right + rng.random_range(top + 1..=bottom - 1) * width,
+            };
+            walls[door] = false;
+        }
+    }
+    walls
+}
+
+/// Places the walls of the feature in the room. Only room tiles become walls, so the doorway
+/// markers stay open.
+pub fn place_interior<R: Rng>(rng: &mut R, feature: InteriorFeature, room: &Room, grid: &mut Grid) {
+    let bounds = room.bounds;
+    let walls = feature_walls(feature, bounds.width, bounds.height, rng);
+    for (tile, _) in walls.iter().enumerate().filter(|&(_, &wall)| wall) {
+        let grid_tile =
+            bounds.x + tile % bounds.width + (bounds.y + tile / bounds.width) * grid.width;
+        if grid.tiles[grid_tile] == Tile::Room {
+            grid.tiles[grid_tile] = Tile::Wall;
+        }
+    }
+}
+
+/// Decorates a proportion of the rooms of suitable size with one of the configured features
+/// which fit in them. Runs after the caves and before the mazes, so rooms which already have
+/// walls inside are skipped and the mazes skip the decorated rooms.
+pub fn make_interiors<R: Rng>(
+    rng: &mut R,
+    configuration: &Configuration,
+    grid: &mut Grid,
+    dungeon: &Dungeon,
+) {
+    let Some(parameters) = &configuration.room_interiors else {
+        return;
+    };
+    for room in &dungeon.rooms {
+        let (width, height) = (room.bounds.width, room.bounds.height);
+        if has_interior(grid, room)
+            || width.min(height) < parameters.min_dimension
+            || width.max(height) > parameters.max_dimension
+            || rng.random_float() >= parameters.chance
+        {
+            continue;
+        }
+        let fitting: Vec<InteriorFeature> = parameters
+            .features
+            .iter()
+            .copied()
+            .filter(|feature| feature.fits(width, height))
+            .collect();
+        if fitting.is_empty() {
+            continue;
+        }
+        let feature = fitting[rng.random_range(0..=fitting.len() - 1)];
+        place_interior(rng, feature, room, grid);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::{label_regions, make_grid};
+    use crate::mock::{MockMinRng, room};
+    use crate::mst::pick_corridors;
+    use crate::room::generate_rooms;
+    use crate::triangulation::triangulate;
+    use crate::vec::vec2u;
+    use crate::verify::verify;
+
+    use rand::SeedableRng;
+
+    #[test]
+    fn deterministic_features() {
+        // A 7x5 room with a doorway marker on its south side.
+        let empty = "\
+            %%%%%%%%%\n\
+            %_______%\n\
+            %_______%\n\
+            %_______%\n\
+            %_______%\n\
+            %__d____%\n\
+            %%%%%%%%%\n";
+        let expected = [
+            (
+                InteriorFeature::Pillars,
+                "\
+                %%%%%%%%%\n\
+                %_______%\n\
+                %_#_#_#_%\n\
+                %_______%\n\
+                %_#_#_#_%\n\
+                %__d____%\n\
+                %%%%%%%%%\n",
+            ),
+            (
+                InteriorFeature::InnerRing,
+                "\
+                %%%%%%%%%\n\
+                %_______%\n\
+                %_#_###_%\n\
+                %_______%\n\
+                %_#_###_%\n\
+                %__d____%\n\
+                %%%%%%%%%\n",
+            ),
+            (
+                InteriorFeature::Split,
+                "\
+                %%%%%%%%%\n\
+                %_______%\n\
+                %__#____%\n\
+                %__#____%\n\
+                %__#____%\n\
+                %__d____%\n\
+                %%%%%%%%%\n",
+            ),
+            (
+                InteriorFeature::NestedRoom,
+                "\
+                %%%%%%%%%\n\
+                %_______%\n\
+                %_#_#___%\n\
+                %_#_#___%\n\
+                %_###___%\n\
+                %__d____%\n\
+                %%%%%%%%%\n",
+            ),
+        ];
+        for (feature, expected) in expected {
+            let mut grid = Grid::parse_lenient(empty);
+            place_interior(&mut MockMinRng, feature, &room(1, 1, 7, 5), &mut grid);
+            assert_eq!(
+                grid.to_string(),
+                expected,
+                "{feature:?} is placed incorrectly."
+            );
+        }
+
+        assert!(
+            InteriorFeature::Pillars.fits(3, 3),
+            "Pillars should fit in small rooms."
+        );
+        assert!(
+            InteriorFeature::Split.fits(3, 5),
+            "A narrow room can be split across."
+        );
+        assert!(
+            !InteriorFeature::NestedRoom.fits(4, 8),
+            "A nested room needs space on every side."
+        );
+    }
+
+    #[test]
+    fn interiors_keep_rooms_connected() {
+        for feature in InteriorFeature::ALL {
+            let configuration = Configuration {
+                room_interiors: Some(RoomInteriors {
+                    chance: 1.0,
+                    features: vec![feature],
+                    ..Default::default()
+                }),
+                maze_chance: 1.0,
+                ..Default::default()
+            };
+            let grid_dimensions = vec2u(100, 100);
+            for seed in 0..10 {
+                let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+                let mut dungeon =
+                    generate_rooms(&configuration, grid_dimensions, Some(20), &mut rng);
+                let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+                let corridors =
+                    pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+                let (mut grid, _) =
+                    make_grid(&configuration, grid_dimensions, &dungeon, &corridors)
+                        .expect("The corridors should be carved.");
+                make_interiors(&mut rng, &configuration, &mut grid, &dungeon);
+                let interiors = grid.tiles.clone();
+                crate::maze::make_mazes(&mut rng, &configuration, &mut grid, &dungeon);
+                assert_eq!(
+                    grid.tiles, interiors,
+                    "Mazes should not be placed in decorated rooms."
+                );
+
+                let verification = verify(&grid, &dungeon);
+                assert!(
+                    verification.is_valid(&configuration),
+                    "{feature:?} with seed {seed} has problems: {verification:?}"
+                );
+                for room in &dungeon.rooms {
+                    let bounds = room.bounds;
+                    let walls: Vec<bool> = grid
+                        .region_tiles(bounds)
+                        .map(|(_, tile)| tile == Tile::Wall)
+                        .collect();
+                    assert!(
+                        walls.iter().any(|&wall| wall),
+                        "Every room should be decorated with {feature:?} (seed {seed})."
+                    );
+                    let (_, sizes) = label_regions(bounds.width, bounds.height, &walls);
+                    assert_eq!(
+                        sizes.len(),
+                        1,
+                        "{feature:?} should not cut off a part of the room (seed {seed})."
+                    );
+                }
+            }
+        }
+    }
+}
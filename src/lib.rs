@@ -2,17 +2,34 @@
 
 pub mod a_star;
 pub mod binary_heap;
+pub mod cave;
+pub mod cost_field;
+pub mod drunkard;
 pub mod grid;
+pub mod interior;
 pub mod maze;
+pub mod maze_fill;
 pub mod mst;
+pub mod navigation;
+pub mod prefab;
 pub mod rng;
 pub mod room;
 pub mod triangulation;
 pub mod vec;
+pub mod verify;
 
 #[cfg(test)]
 pub mod mock;
 
+use cave::CellularCave;
+use drunkard::DrunkardWalk;
+use interior::RoomInteriors;
+use maze::MazeAlgorithm;
+use maze_fill::MazeFill;
+use mst::{EdgeMetric, LoopPreference};
+use prefab::PrefabStamps;
+use room::RoomArchetype;
+
 #[derive(Clone, Debug)]
 pub struct Configuration {
     /// Minimum tile length of a room. Valid for both width and height.
@@ -30,7 +47,40 @@ pub struct Configuration {
     /// What proportion of edges on average should be reintroduced as corridors i.e. (0) out of
     /// every (1).
     pub reintroduced_corridor_density: (usize, usize),
-    /// Whether the corridors are allowed to make 2x2 squares.
+    /// The exact number of edges which are not part of the minimum spanning tree that should be
+    /// reintroduced as corridors, picked uniformly from the inclusive range (0)..=(1). Each of
+    /// them creates an extra loop. Edges reintroduced for the minimum room degree are included.
+    /// When set, the density is ignored.
+    pub reintroduced_corridor_count: Option<(usize, usize)>,
+    /// Which edges are reintroduced first when the reintroduced corridor count is set.
+    pub loop_preference: LoopPreference,
+    /// How the edges of the triangulation are weighted when picking the minimum spanning tree.
+    pub edge_metric: EdgeMetric,
+    /// Each edge weight is increased by a random percentage between 0 and this value, which
+    /// makes the minimum spanning tree less predictable.
+    pub edge_weight_jitter: usize,
+    /// The minimum and maximum number of corridors connected to a room. The maximum is exceeded
+    /// only when it is needed to keep all rooms connected and the minimum is reached only when the
+    /// triangulation has enough edges.
+    pub room_degree: (usize, usize),
+    /// Rooms which match one of the archetypes use the corridor limits (and the maze algorithm,
+    /// if it has one) of the first such archetype instead of the global ones.
+    pub room_archetypes: Vec<RoomArchetype>,
+    /// Width of the corridors in tiles. Must not be greater than the minimum room dimension, since
+    /// the doorways are widened to match. Wider corridors are carved as a square of this width at
+    /// every tile of the path.
+    pub corridor_width: usize,
+    /// Width of the corridors which are not needed to connect the rooms, e.g. the reintroduced
+    /// ones. Uses the corridor width when not set.
+    pub reintroduced_corridor_width: Option<usize>,
+    /// The maximum number of turns of a corridor. Corridors which break this limit are rerouted
+    /// or, if the rooms are connected through other corridors, dropped.
+    pub max_corridor_turns: Option<usize>,
+    /// The maximum length of a corridor as a percentage of the Manhattan distance between its
+    /// doorways. Corridors which break this limit are handled like the ones with too many turns.
+    pub max_corridor_stretch: Option<usize>,
+    /// Whether the corridors are allowed to make 2x2 squares. Applies only to corridors which are
+    /// one tile wide.
     pub disallow_corridor_squares: bool,
     /// Cost for the A* algorithm when we go through an already placed corridor. The relationship
     /// between this value and the other two costs determines the shape of the corridors.
@@ -39,14 +89,43 @@ pub struct Configuration {
     /// or vertical) from which we came to the current tile. When lower than the standard cost
     /// makes the corridors straight hence the name.
     pub straight_cost: usize,
-    /// Default cost for the A* algorithm. The corridors can move only horizontally or vertically.
+    /// Default cost for the A* algorithm. The corridors can move only horizontally or vertically
+    /// unless diagonal corridors are enabled.
     pub standard_cost: usize,
+    /// Whether the corridors can also move diagonally. A diagonal step cannot squeeze between the
+    /// corner of a room and another obstacle. Every diagonal step is carved with one of its corner
+    /// tiles, so the corridors can be walked with horizontal and vertical steps.
+    pub diagonal_corridors: bool,
+    /// Cost for the A* algorithm when the corridor moves diagonally.
+    pub diagonal_cost: usize,
+    /// Parameters of the random walk carver, which make_cave_grid uses instead of the A*
+    /// algorithm. When set, the generator makes cave grids; make_grid ignores this.
+    pub drunkard_walk: Option<DrunkardWalk>,
+    /// Parameters of the filler which grows maze passages in the empty space between the rooms
+    /// and the corridors after they are carved. No passages are grown when not set.
+    pub maze_fill: Option<MazeFill>,
+    /// Parameters of the cellular automaton which turns some of the rooms into caves. No caves
+    /// are made when not set. Mazes are not placed in caves.
+    pub cellular_cave: Option<CellularCave>,
+    /// Parameters of the walls which decorate the inside of some of the rooms. No interiors are
+    /// placed when not set. Mazes are not placed in decorated rooms.
+    pub room_interiors: Option<RoomInteriors>,
+    /// The prefab library and how often its prefabs replace rooms or are stamped inside them.
+    pub prefabs: PrefabStamps,
     /// The minimum room dimensions required for a room to be considered for maze generation.
     pub min_maze_dimension: usize,
     /// The maximum room dimensions required for a room to be considered for maze generation.
     pub max_maze_dimension: usize,
     /// What proportion of legal rooms should have mazes in them.
     pub maze_chance: f32,
+    /// The algorithm which carves the mazes of rooms which do not match an archetype with its
+    /// own algorithm.
+    pub maze_algorithm: MazeAlgorithm,
+    /// What proportion of the dead ends of the mazes are removed by opening one of their walls.
+    /// Removing dead ends makes loops in the mazes.
+    pub maze_braid: f32,
+    /// Favors horizontal (up to 1) or vertical (down to -1) passages in the mazes.
+    pub maze_bias: f32,
 }
 
 impl Configuration {
@@ -57,13 +136,46 @@ impl Configuration {
             && self.doorway_offset >= 1
             && self.reintroduced_corridor_density.0 <= self.reintroduced_corridor_density.1
             && self.reintroduced_corridor_density.1 >= 1
+            && self
+                .reintroduced_corridor_count
+                .is_none_or(|(min_count, max_count)| min_count <= max_count)
+            && self.room_degree.0 <= self.room_degree.1
+            && self.room_archetypes.iter().all(|archetype| {
+                archetype.min_dimension <= archetype.max_dimension
+                    && archetype.degree.0 <= archetype.degree.1
+            })
+            && [Some(self.corridor_width), self.reintroduced_corridor_width]
+                .iter()
+                .flatten()
+                .all(|&width| width >= 1 && width <= self.min_room_dimension)
+            && self
+                .max_corridor_stretch
+                .is_none_or(|max_stretch| max_stretch >= 100)
             && self.corridor_cost >= 1
             && self.straight_cost >= 1
             && self.standard_cost >= 1
+            && self.diagonal_cost >= 1
+            && self
+                .drunkard_walk
+                .is_none_or(|drunkard_walk| drunkard_walk.bias <= 100)
+            && self.maze_fill.is_none_or(|maze_fill| {
+                maze_fill.straightness <= 100 && (0.0..=1.0).contains(&maze_fill.dead_end_pruning)
+            })
+            && self
+                .cellular_cave
+                .is_none_or(|cave| (0.0..=1.0).contains(&cave.chance) && cave.fill_percent <= 100)
+            && self.room_interiors.as_ref().is_none_or(|interiors| {
+                (0.0..=1.0).contains(&interiors.chance)
+                    && interiors.min_dimension <= interiors.max_dimension
+            })
+            && (0.0..=1.0).contains(&self.prefabs.room_chance)
+            && (0.0..=1.0).contains(&self.prefabs.stamp_chance)
             && self.min_maze_dimension >= 5
             && self.min_room_dimension <= self.min_maze_dimension
             && 0.0 <= self.maze_chance
             && self.maze_chance <= 1.0
+            && (0.0..=1.0).contains(&self.maze_braid)
+            && (-1.0..=1.0).contains(&self.maze_bias)
     }
 }
 
@@ -76,13 +188,33 @@ impl Default for Configuration {
             doorway_offset: 2,
             max_fail_count: 10,
             reintroduced_corridor_density: (1, 2),
+            reintroduced_corridor_count: None,
+            loop_preference: LoopPreference::Random,
+            edge_metric: EdgeMetric::Euclidean,
+            edge_weight_jitter: 0,
+            room_degree: (0, usize::MAX),
+            room_archetypes: vec![],
+            corridor_width: 1,
+            reintroduced_corridor_width: None,
+            max_corridor_turns: None,
+            max_corridor_stretch: None,
             disallow_corridor_squares: true,
             corridor_cost: 1,
             straight_cost: 2,
             standard_cost: 3,
+            diagonal_corridors: false,
+            diagonal_cost: 4,
+            drunkard_walk: None,
+            maze_fill: None,
+            cellular_cave: None,
+            room_interiors: None,
+            prefabs: PrefabStamps::default(),
             min_maze_dimension: 5,
             max_maze_dimension: 20,
             maze_chance: 0.1,
+            maze_algorithm: MazeAlgorithm::Kruskal,
+            maze_braid: 0.0,
+            maze_bias: 0.0,
         }
     }
 }
@@ -1,63 +1,382 @@
 use crate::Configuration;
 use crate::grid::{Grid, Tile};
 use crate::mst::DisjointSet;
+use crate::rng::Rng;
 use crate::room::{Dungeon, Room};
 use crate::vec;
 
-use rand::Rng;
-use rand::distr::Distribution;
-use rand::seq::SliceRandom;
+use std::collections::VecDeque;
 
-/// Uses the Disjoint Set structure to construct a maze in a room in a similar fassion to
-/// [this article](https://en.wikipedia.org/wiki/Maze_generation_algorithm#Iterative_randomized_Kruskal's_algorithm_(with_sets)).
-#[cfg(not(tarpaulin_include))]
-pub fn place_maze<R: Rng>(rng: &mut R, room: &Room, grid: &mut Grid) {
-    let northwest_corner = vec::to_index(vec::vec2u(room.bounds.x, room.bounds.y), grid.width);
-    let room_width = room.bounds.width;
-    let room_height = room.bounds.height;
+/// The algorithm which carves the passages of a maze. All of them make perfect mazes (spanning
+/// trees of the cells), but their passages have different shapes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MazeAlgorithm {
+    /// Randomized Kruskal's algorithm. Many short dead ends.
+    #[default]
+    Kruskal,
+    /// Randomized depth first search. Long winding passages with few dead ends.
+    RecursiveBacktracker,
+    /// Randomized Prim's algorithm. Passages branch out from a single cell.
+    Prim,
+    /// Loop-erased random walks, which pick one of all the possible mazes uniformly at random.
+    Wilson,
+    /// Builds the maze row by row, remembering only which cells of the current row are connected.
+    Eller,
+    /// Every cell opens to the north or to the west. The north and the west edges are straight
+    /// passages and the maze is biased towards the north-west corner.
+    BinaryTree,
+}
 
-    // The maze generation algorithm works with 2x2 tiless. To cover the whole room we need the
-    // following maze dimensions.
-    let maze_width = (room_width >> 1) + (room_width & 1);
-    let maze_height = (room_height >> 1) + (room_height & 1);
+impl MazeAlgorithm {
+    /// All the algorithms.
+    pub const ALL: [MazeAlgorithm; 6] = [
+        MazeAlgorithm::Kruskal,
+        MazeAlgorithm::RecursiveBacktracker,
+        MazeAlgorithm::Prim,
+        MazeAlgorithm::Wilson,
+        MazeAlgorithm::Eller,
+        MazeAlgorithm::BinaryTree,
+    ];
+}
+
+/// The passages of a maze of cells. Every cell can be open to its east and to its south neighbor.
+struct Passages {
+    width: usize,
+    height: usize,
+    east: Vec<bool>,
+    south: Vec<bool>,
+}
+
+impl Passages {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            east: vec![false; width * height],
+            south: vec![false; width * height],
+        }
+    }
 
-    let mut edges: Vec<(usize, usize)> =
-        Vec::with_capacity((2 * maze_width * maze_height) - maze_width - maze_height);
-    for column in 0..maze_width - 1 {
-        for row in 0..maze_height - 1 {
-            let northwest_tile = column + row * maze_width;
-            edges.push((northwest_tile, northwest_tile + 1));
-            edges.push((northwest_tile, northwest_tile + maze_width));
+    /// Opens the passage between two neighboring cells.
+    fn open(&mut self, a: usize, b: usize) {
+        let (a, b) = (a.min(b), a.max(b));
+        if b - a == 1 {
+            self.east[a] = true;
+        } else {
+            self.south[a] = true;
         }
     }
-    for column in 0..maze_width - 1 {
-        let west_tile = column + (maze_height - 1) * maze_width;
-        edges.push((west_tile, west_tile + 1));
+
+    fn is_open(&self, a: usize, b: usize) -> bool {
+        let (a, b) = (a.min(b), a.max(b));
+        if b - a == 1 {
+            self.east[a]
+        } else {
+            self.south[a]
+        }
     }
-    for row in 0..maze_height - 1 {
-        let north_tile = (maze_width - 1) + row * maze_width;
-        edges.push((north_tile, north_tile + maze_width));
+
+    /// The cells next to the cell in the maze.
+    fn neighbors(&self, cell: usize) -> impl Iterator<Item = usize> + use<> {
+        let (column, row) = (cell % self.width, cell / self.width);
+        let width = self.width;
+        [
+            (column + 1 < width).then(|| cell + 1),
+            (column > 0).then(|| cell - 1),
+            (row + 1 < self.height).then(|| cell + width),
+            (row > 0).then(|| cell - width),
+        ]
+        .into_iter()
+        .flatten()
     }
-    edges.shuffle(rng);
-    let tile_count = maze_width * maze_height;
-    let mut disjoint_set = DisjointSet::new(tile_count);
 
-    // Since the rooms are already empty, we need the edges between tiles which would place walls
-    // i.e. the edges which are not part of the spanning tree.
-
-    let mut current_edge = 0;
-    let mut added_edges = 1;
-    while current_edge < edges.len() && added_edges < tile_count {
-        let tile_a = edges[current_edge].0;
-        let tile_b = edges[current_edge].1;
-        if disjoint_set.find_set(tile_a) == disjoint_set.find_set(tile_b) {
-            current_edge += 1;
+    fn random_neighbor<R: Rng>(&self, cell: usize, bias: f32, rng: &mut R) -> usize {
+        let passages: Vec<(usize, usize)> = self
+            .neighbors(cell)
+            .map(|neighbor| (cell, neighbor))
+            .collect();
+        passages[pick_passage(bias, &passages, rng)].1
+    }
+
+    fn open_passage_count(&self, cell: usize) -> usize {
+        self.neighbors(cell)
+            .filter(|&neighbor| self.is_open(cell, neighbor))
+            .count()
+    }
+}
+
+/// The weight of a passage between two neighboring cells given the bias, which goes from -1
+/// (vertical passages) to 1 (horizontal passages). The weights never reach zero, otherwise random
+/// walks could get stuck in a single row or column.
+fn passage_weight(bias: f32, a: usize, b: usize) -> f32 {
+    let weight = if a.abs_diff(b) == 1 {
+        1.0 + bias
+    } else {
+        1.0 - bias
+    };
+    weight.max(0.05)
+}
+
+/// Picks the index of one of the passages at random, favoring the direction of the bias.
+fn pick_passage<R: Rng>(bias: f32, passages: &[(usize, usize)], rng: &mut R) -> usize {
+    let total: f32 = passages
+        .iter()
+        .map(|&(a, b)| passage_weight(bias, a, b))
+        .sum();
+    if bias == 0.0 || total <= 0.0 {
+        return rng.random_range(0..=passages.len() - 1);
+    }
+    let mut target = rng.random_float() * total;
+    for (index, &(a, b)) in passages.iter().enumerate() {
+        let weight = passage_weight(bias, a, b);
+        if target < weight {
+            return index;
+        }
+        target -= weight;
+    }
+    passages.len() - 1
+}
+
+/// Carves the passages of a maze of the given dimensions (in cells) with the algorithm. The bias
+/// favors horizontal (positive) or vertical (negative) passages.
+fn carve_passages<R: Rng>(
+    algorithm: MazeAlgorithm,
+    width: usize,
+    height: usize,
+    bias: f32,
+    rng: &mut R,
+) -> Passages {
+    let mut passages = Passages::new(width, height);
+    let cell_count = width * height;
+    match algorithm {
+        MazeAlgorithm::Kruskal => {
+            let mut edges: Vec<(usize, usize)> = (0..cell_count)
+                .flat_map(|cell| passages.neighbors(cell).map(move |other| (cell, other)))
+                .filter(|&(cell, other)| cell < other)
+                .collect();
+            if bias == 0.0 {
+                rng.shuffle(&mut edges);
+            } else {
+                // A weighted shuffle: heavier passages tend to get bigger keys and come first.
+                let mut keyed: Vec<(f32, (usize, usize))> = edges
+                    .iter()
+                    .map(|&(a, b)| {
+                        let key = rng.random_float().powf(1.0 / passage_weight(bias, a, b));
+                        (key, (a, b))
+                    })
+                    .collect();
+                keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+                edges = keyed.into_iter().map(|(_, edge)| edge).collect();
+            }
+            let mut disjoint_set = DisjointSet::new(cell_count);
+            for (a, b) in edges {
+                if disjoint_set.find_set(a) != disjoint_set.find_set(b) {
+                    disjoint_set.union_sets(a, b);
+                    passages.open(a, b);
+                }
+            }
+        }
+        MazeAlgorithm::RecursiveBacktracker => {
+            let mut visited = vec![false; cell_count];
+            let start = rng.random_range(0..=cell_count - 1);
+            visited[start] = true;
+            let mut stack = vec![start];
+            while let Some(&cell) = stack.last() {
+                let unvisited: Vec<(usize, usize)> = passages
+                    .neighbors(cell)
+                    .filter(|&neighbor| !visited[neighbor])
+                    .map(|neighbor| (cell, neighbor))
+                    .collect();
+                if unvisited.is_empty() {
+                    stack.pop();
+                    continue;
+                }
+                let next = unvisited[pick_passage(bias, &unvisited, rng)].1;
+                passages.open(cell, next);
+                visited[next] = true;
+                stack.push(next);
+            }
+        }
+        MazeAlgorithm::Prim => {
+            let mut visited = vec![false; cell_count];
+            let start = rng.random_range(0..=cell_count - 1);
+            visited[start] = true;
+            let mut frontier: Vec<(usize, usize)> = passages
+                .neighbors(start)
+                .map(|neighbor| (start, neighbor))
+                .collect();
+            while !frontier.is_empty() {
+                let (cell, next) = frontier.swap_remove(pick_passage(bias, &frontier, rng));
+                if visited[next] {
+                    continue;
+                }
+                passages.open(cell, next);
+                visited[next] = true;
+                frontier.extend(
+                    passages
+                        .neighbors(next)
+                        .filter(|&neighbor| !visited[neighbor])
+                        .map(|neighbor| (next, neighbor)),
+                );
+            }
+        }
+        MazeAlgorithm::Wilson => {
+            let mut in_maze = vec![false; cell_count];
+            in_maze[rng.random_range(0..=cell_count - 1)] = true;
+            // The direction in which the walk last left every cell. Overwriting it when the walk
+            // comes back to a cell erases the loop.
+            let mut next = vec![0; cell_count];
+            for start in 0..cell_count {
+                let mut cell = start;
+                while !in_maze[cell] {
+                    next[cell] = passages.random_neighbor(cell, bias, rng);
+                    cell = next[cell];
+                }
+                let mut cell = start;
+                while !in_maze[cell] {
+                    in_maze[cell] = true;
+                    passages.open(cell, next[cell]);
+                    cell = next[cell];
+                }
+            }
+        }
+        MazeAlgorithm::Eller => {
+            let horizontal_chance = (0.5 * (1.0 + bias as f64)).clamp(0.0, 1.0);
+            let vertical_chance = (0.5 * (1.0 - bias as f64)).clamp(0.0, 1.0);
+            let mut disjoint_set = DisjointSet::new(cell_count);
+            for row in 0..height {
+                let last_row = row + 1 == height;
+                let first_cell = row * width;
+                // Join neighboring cells which are not connected yet, all of them in the last row.
+                for cell in first_cell..first_cell + width - 1 {
+                    if disjoint_set.find_set(cell) != disjoint_set.find_set(cell + 1)
+                        && (last_row || rng.random_bool(horizontal_chance))
+                    {
+                        disjoint_set.union_sets(cell, cell + 1);
+                        passages.open(cell, cell + 1);
+                    }
+                }
+                if last_row {
+                    break;
+                }
+                // Every set continues to the next row through at least one cell.
+                let mut cells: Vec<(usize, usize)> = (first_cell..first_cell + width)
+                    .map(|cell| (cell, disjoint_set.find_set(cell)))
+                    .collect();
+                rng.shuffle(&mut cells);
+                let mut continued = vec![false; cell_count];
+                for (cell, set) in cells {
+                    if !continued[set] || rng.random_bool(vertical_chance) {
+                        continued[set] = true;
+                        disjoint_set.union_sets(cell, cell + width);
+                        passages.open(cell, cell + width);
+                    }
+                }
+            }
+        }
+        MazeAlgorithm::BinaryTree => {
+            for cell in 0..cell_count {
+                let (column, row) = (cell % width, cell / width);
+                let open_north = match (column > 0, row > 0) {
+                    (false, false) => continue,
+                    (true, true) => rng.random_bool((0.5 * (1.0 - bias as f64)).clamp(0.0, 1.0)),
+                    (west, _) => !west,
+                };
+                if open_north {
+                    passages.open(cell, cell - width);
+                } else {
+                    passages.open(cell, cell - 1);
+                }
+            }
+        }
+    }
+    passages
+}
+
+/// Removes the given fraction of the dead ends of the maze by opening one of their walls,
+/// preferably towards another dead end, so that a single opening removes both of them.
+fn braid<R: Rng>(passages: &mut Passages, braid: f32, rng: &mut R) {
+    let mut dead_ends: Vec<usize> = (0..passages.width * passages.height)
+        .filter(|&cell| passages.open_passage_count(cell) == 1)
+        .collect();
+    rng.shuffle(&mut dead_ends);
+    let quota = (braid.clamp(0.0, 1.0) * dead_ends.len() as f32).round() as usize;
+
+    let mut removed = 0;
+    for cell in dead_ends {
+        if removed >= quota {
+            break;
+        }
+        // An earlier opening may have already removed this dead end.
+        if passages.open_passage_count(cell) != 1 {
             continue;
         }
-        added_edges += 1;
-        edges.swap_remove(current_edge);
-        disjoint_set.union_sets(tile_a, tile_b);
+        let closed: Vec<usize> = passages
+            .neighbors(cell)
+            .filter(|&neighbor| !passages.is_open(cell, neighbor))
+            .collect();
+        let dead_neighbors: Vec<usize> = closed
+            .iter()
+            .copied()
+            .filter(|&neighbor| passages.open_passage_count(neighbor) == 1)
+            .collect();
+        let candidates = if dead_neighbors.is_empty() {
+            closed
+        } else {
+            dead_neighbors
+        };
+        let other = candidates[rng.random_range(0..=candidates.len() - 1)];
+        if passages.open_passage_count(other) == 1 {
+            removed += 1;
+        }
+        passages.open(cell, other);
+        removed += 1;
     }
+}
+
+/// Constructs a maze in a room with the given algorithm and removes some of its dead ends as set
+/// in the configuration. Any of the algorithms of MazeAlgorithm can be selected: randomized
+/// Kruskal's algorithm (as in
+/// [this article](https://en.wikipedia.org/wiki/Maze_generation_algorithm#Iterative_randomized_Kruskal's_algorithm_(with_sets))),
+/// the recursive backtracker, Prim's, Wilson's and Eller's algorithm and the binary tree algorithm.
+pub fn place_maze<R: Rng>(
+    rng: &mut R,
+    configuration: &Configuration,
+    algorithm: MazeAlgorithm,
+    room: &Room,
+    grid: &mut Grid,
+) {
+    let northwest_corner = vec::to_index(vec::vec2u(room.bounds.x, room.bounds.y), grid.width);
+    let room_width = room.bounds.width;
+    let room_height = room.bounds.height;
+
+    // The maze generation algorithm works with 2x2 tiless. To cover the whole room we need the
+    // following maze dimensions.
+    let maze_width = (room_width >> 1) + (room_width & 1);
+    let maze_height = (room_height >> 1) + (room_height & 1);
+    let mut passages = carve_passages(
+        algorithm,
+        maze_width,
+        maze_height,
+        configuration.maze_bias,
+        rng,
+    );
+    braid(&mut passages, configuration.maze_braid, rng);
+
+    // Since the rooms are already empty, we need the edges between tiles which would place walls
+    // i.e. the edges which are not passages of the maze.
+    let mut edges: Vec<(usize, usize)> = (0..maze_width * maze_height)
+        .flat_map(|cell| [(cell, cell + 1), (cell, cell + maze_width)])
+        .filter(|&(cell, other)| {
+            let east = other == cell + 1;
+            let inside = if east {
+                cell % maze_width + 1 < maze_width
+            } else {
+                other < maze_width * maze_height
+            };
+            inside && !passages.is_open(cell, other)
+        })
+        .collect();
 
     // Add fake edges to make walls on the south and east wall of the room if its dimensions are
     // divisible by 2. (Removes an ugly artefact in this case.)
@@ -121,23 +440,433 @@ pub fn place_maze<R: Rng>(rng: &mut R, room: &Room, grid: &mut Grid) {
     }
 }
 
-#[cfg(not(tarpaulin_include))]
+/// Finds the entrances of the room from the grid alone: the tiles inside the room next to any
+/// doorway tile of the ring around it, including doorways which are not part of the dungeon (e.g.
+/// those of the maze filler) and every tile of wide doorways.
+pub(crate) fn ring_entrances(grid: &Grid, room: &Room) -> Vec<usize> {
+    let bounds = room.bounds;
+    let width = grid.width;
+    (bounds.y..bounds.y + bounds.height)
+        .flat_map(|y| (bounds.x..bounds.x + bounds.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            [
+                (x + 1 == bounds.x + bounds.width, x + 1 + y * width),
+                (x == bounds.x, (x + y * width).wrapping_sub(1)),
+                (y + 1 == bounds.y + bounds.height, x + (y + 1) * width),
+                (y == bounds.y, (x + y * width).wrapping_sub(width)),
+            ]
+            .into_iter()
+            .any(|(on_side, outside)| on_side && grid.tiles.get(outside) == Some(&Tile::Doorway))
+        })
+        .map(|(x, y)| x + y * width)
+        .collect()
+}
+
+/// Makes sure that every entrance of the room can be reached from the first one. Entrances which
+/// the maze has cut off are connected by removing the fewest walls possible, found with a 0-1
+/// breadth first search in which stepping on a wall costs 1 and stepping on any other tile of the
+/// room costs 0. Returns the number of removed walls.
+pub fn connect_entrances(room: &Room, entrances: &[usize], grid: &mut Grid) -> usize {
+    let Some(&first) = entrances.first() else {
+        return 0;
+    };
+    let width = grid.width;
+    let bounds = room.bounds;
+    // The buffers cover only the room, so the tiles of the grid are mapped to the room.
+    let local = |tile: usize| tile % width - bounds.x + (tile / width - bounds.y) * bounds.width;
+    let neighbors = |tile: usize| {
+        let (x, y) = (tile % width, tile / width);
+        [
+            (x + 1 < bounds.x + bounds.width).then(|| tile + 1),
+            (x > bounds.x).then(|| tile - 1),
+            (y + 1 < bounds.y + bounds.height).then(|| tile + width),
+            (y > bounds.y).then(|| tile - width),
+        ]
+        .into_iter()
+        .flatten()
+    };
+
+    let mut removed_walls = 0;
+    let mut walls = vec![usize::MAX; bounds.width * bounds.height];
+    let mut parent = vec![usize::MAX; bounds.width * bounds.height];
+    let mut queue = VecDeque::new();
+    loop {
+        // The fewest walls between the first entrance and every tile of the room.
+        walls.fill(usize::MAX);
+        walls[local(first)] = 0;
+        queue.push_back(first);
+        while let Some(current) = queue.pop_front() {
+            for neighbor in neighbors(current) {
+                let cost = match grid.tiles[neighbor] {
+                    Tile::Wall => 1,
+                    Tile::Room | Tile::Doorway => 0,
+                    _ => continue,
+                };
+                if walls[local(current)] + cost < walls[local(neighbor)] {
+                    walls[local(neighbor)] = walls[local(current)] + cost;
+                    parent[local(neighbor)] = current;
+                    if cost == 0 {
+                        queue.push_front(neighbor);
+                    } else {
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        // Connect the closest cut off entrance and search again, since the removed walls may
+        // also connect other entrances.
+        let Some((cut_off, _)) = entrances
+            .iter()
+            .map(|&entrance| (entrance, walls[local(entrance)]))
+            .filter(|&(_, walls)| walls > 0 && walls != usize::MAX)
+            .min_by_key(|&(_, walls)| walls)
+        else {
+            return removed_walls;
+        };
+        let mut current = cut_off;
+        while current != first {
+            if grid.tiles[current] == Tile::Wall {
+                grid.tiles[current] = Tile::Room;
+                removed_walls += 1;
+            }
+            current = parent[local(current)];
+        }
+    }
+}
+
+/// Answers whether the room already has walls inside, e.g. because it is a cave.
+pub(crate) fn has_interior(grid: &Grid, room: &Room) -> bool {
+    grid.region_tiles(room.bounds)
+        .any(|(_, tile)| tile == Tile::Wall)
+}
+
+/// Places mazes in a proportion of the rooms of suitable size. Rooms which already have walls
+/// inside are skipped. Every entrance of the room is taken from the grid, so that wide doorways and
+/// the doorways of the maze filler stay connected too.
 pub fn make_mazes<R: Rng>(
     rng: &mut R,
     configuration: &Configuration,
     grid: &mut Grid,
     room_graph: &Dungeon,
 ) {
-    let uniform = rand::distr::Uniform::new(0.0, 1.0).expect("Uniform range should be ok.");
-
     for room in &room_graph.rooms {
-        if room.bounds.width >= configuration.min_maze_dimension
+        if !has_interior(grid, room)
+            && room.bounds.width >= configuration.min_maze_dimension
             && room.bounds.height >= configuration.min_maze_dimension
             && room.bounds.width <= configuration.max_maze_dimension
             && room.bounds.height <= configuration.max_maze_dimension
-            && uniform.sample(rng) < configuration.maze_chance
+            && rng.random_float() < configuration.maze_chance
         {
-            place_maze(rng, room, grid);
+            let algorithm = configuration
+                .room_archetypes
+                .iter()
+                .find(|archetype| archetype.matches(room))
+                .and_then(|archetype| archetype.maze_algorithm)
+                .unwrap_or(configuration.maze_algorithm);
+            place_maze(rng, configuration, algorithm, room, grid);
+            connect_entrances(room, &ring_entrances(grid, room), grid);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::{label_regions, make_grid};
+    use crate::mock::{MockMinRng, doorway, room};
+    use crate::mst::pick_corridors;
+    use crate::room::generate_rooms;
+    use crate::triangulation::triangulate;
+    use crate::vec::vec2u;
+    use crate::verify::verify;
+
+    use rand::SeedableRng;
+
+    #[test]
+    fn algorithms_make_spanning_trees() {
+        for algorithm in MazeAlgorithm::ALL {
+            for seed in 0..10 {
+                let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+                let (width, height) = (7, 5);
+                let passages = carve_passages(algorithm, width, height, 0.0, &mut rng);
+
+                let passage_count = (0..width * height)
+                    .flat_map(|cell| passages.neighbors(cell).map(move |other| (cell, other)))
+                    .filter(|&(cell, other)| cell < other && passages.is_open(cell, other))
+                    .count();
+                assert_eq!(
+                    passage_count,
+                    width * height - 1,
+                    "{algorithm:?} should open one passage less than there are cells."
+                );
+
+                // With one passage less than there are cells, the maze is a tree if every cell
+                // can be reached.
+                let mut reached = vec![false; width * height];
+                reached[0] = true;
+                let mut stack = vec![0];
+                while let Some(cell) = stack.pop() {
+                    for other in passages.neighbors(cell) {
+                        if passages.is_open(cell, other) && !reached[other] {
+                            reached[other] = true;
+                            stack.push(other);
+                        }
+                    }
+                }
+                assert!(
+                    reached.iter().all(|&reached| reached),
+                    "{algorithm:?} should connect every cell (seed {seed})."
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn binary_tree_bias() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let passages = carve_passages(MazeAlgorithm::BinaryTree, 6, 4, 0.0, &mut rng);
+        assert!(
+            (0..5).all(|cell| passages.is_open(cell, cell + 1)),
+            "The north edge should be a straight passage."
+        );
+        assert!(
+            (0..3).all(|row| passages.is_open(row * 6, row * 6 + 6)),
+            "The west edge should be a straight passage."
+        );
+    }
+
+    #[test]
+    fn bias_and_braid() {
+        let (width, height) = (9, 9);
+        let count_passages = |passages: &Passages| {
+            let (mut horizontal, mut vertical) = (0, 0);
+            for cell in 0..width * height {
+                if cell % width + 1 < width && passages.is_open(cell, cell + 1) {
+                    horizontal += 1;
+                }
+                if cell + width < width * height && passages.is_open(cell, cell + width) {
+                    vertical += 1;
+                }
+            }
+            (horizontal, vertical)
+        };
+        let dead_end_count = |passages: &Passages| {
+            (0..width * height)
+                .filter(|&cell| passages.open_passage_count(cell) == 1)
+                .count()
+        };
+
+        for algorithm in MazeAlgorithm::ALL {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+            let horizontal = carve_passages(algorithm, width, height, 1.0, &mut rng);
+            let vertical = carve_passages(algorithm, width, height, -1.0, &mut rng);
+            let (horizontal_count, other_count) = count_passages(&horizontal);
+            let (_, vertical_count) = count_passages(&vertical);
+            // A spanning tree of 9x9 cells has 80 passages, half of them in each direction would
+            // be 40.
+            assert!(
+                horizontal_count > 50 && vertical_count > 50,
+                "{algorithm:?} should favor the direction of the bias \
+                 ({horizontal_count} horizontal, {vertical_count} vertical)."
+            );
+            assert_eq!(
+                horizontal_count + other_count,
+                width * height - 1,
+                "{algorithm:?} should still make a spanning tree with a bias."
+            );
+
+            let mut passages = carve_passages(algorithm, width, height, 0.0, &mut rng);
+            let dead_ends = dead_end_count(&passages);
+            braid(&mut passages, 0.0, &mut rng);
+            assert_eq!(
+                dead_end_count(&passages),
+                dead_ends,
+                "No dead ends should be removed without braiding."
+            );
+            braid(&mut passages, 0.5, &mut rng);
+            assert!(
+                dead_end_count(&passages) <= dead_ends / 2 + 1,
+                "{algorithm:?} should have about half of its dead ends after braiding."
+            );
+            braid(&mut passages, 1.0, &mut rng);
+            assert_eq!(
+                dead_end_count(&passages),
+                0,
+                "{algorithm:?} should have no dead ends after braiding fully."
+            );
+        }
+    }
+
+    #[test]
+    fn cut_off_entrances_are_connected() {
+        let dungeon = Dungeon {
+            rooms: vec![room(2, 2, 7, 3)],
+            // The last doorway is not used by any corridor.
+            doorways: vec![doorway(1, 3, 0), doorway(9, 3, 0), doorway(4, 1, 0)],
+        };
+        let mut grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %%%%%%%%%%%\n\
+            %%___#___%%\n\
+            %d___#___d%\n\
+            %%___#___%%\n\
+            %%%%%%%%%%%\n\
+            %%%%%%%%%%%\n",
+        );
+        let entrances = ring_entrances(&grid, &dungeon.rooms[0]);
+        assert_eq!(
+            entrances,
+            vec![3 * 11 + 2, 3 * 11 + 8],
+            "Only the tiles next to the used doorways are entrances."
+        );
+
+        assert_eq!(
+            connect_entrances(&dungeon.rooms[0], &entrances, &mut grid),
+            1,
+            "A single wall separates the entrances."
+        );
+        assert_eq!(
+            grid.tiles
+                .iter()
+                .filter(|&&tile| tile == Tile::Wall)
+                .count(),
+            2,
+            "The other walls should stay."
+        );
+        assert_eq!(
+            connect_entrances(&dungeon.rooms[0], &entrances, &mut grid),
+            0,
+            "The entrances are already connected."
+        );
+        let verification = verify(&grid, &dungeon);
+        assert!(
+            verification.cut_off_doorways.is_empty(),
+            "No doorway should be cut off: {verification:?}"
+        );
+    }
+
+    #[test]
+    fn doorways_outside_the_dungeon_stay_connected() {
+        // Only the west doorway and the lower tile of the wide east doorway are part of the
+        // dungeon. The south doorway is one of the maze filler and has no marker inside the room,
+        // so the maze always places a wall next to it, which has to be removed.
+        let dungeon = Dungeon {
+            rooms: vec![room(2, 3, 8, 6)],
+            doorways: vec![doorway(1, 5, 0), doorway(10, 5, 0)],
+        };
+        let empty = "\
+            %%%%%%%%%%%%\n\
+            %%%%%%%%%%%%\n\
+            %%%%%%%%%%%%\n\
+            %%________%%\n\
+            %%_______ddc\n\
+            cdd______ddc\n\
+            %%________%%\n\
+            %%________%%\n\
+            %%________%%\n\
+            %%%%%d%%%%%%\n\
+            %%%%%c%%%%%%\n";
+        for algorithm in MazeAlgorithm::ALL {
+            let configuration = Configuration {
+                min_maze_dimension: 3,
+                maze_chance: 1.0,
+                maze_algorithm: algorithm,
+                ..Default::default()
+            };
+            for seed in 0..20 {
+                let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+                let mut grid = Grid::parse_lenient(empty);
+                let room = &dungeon.rooms[0];
+                let entrances = ring_entrances(&grid, room);
+                assert_eq!(entrances.len(), 4, "Every doorway tile has an entrance.");
+                make_mazes(&mut rng, &configuration, &mut grid, &dungeon);
+                assert!(has_interior(&grid, room), "The room should have a maze.");
+
+                let bounds = room.bounds;
+                let walls: Vec<bool> = grid
+                    .region_tiles(bounds)
+                    .map(|(_, tile)| tile == Tile::Wall)
+                    .collect();
+                let (labels, _) = label_regions(bounds.width, bounds.height, &walls);
+                let label = |tile: usize| {
+                    labels[tile % grid.width - bounds.x
+                        + (tile / grid.width - bounds.y) * bounds.width]
+                };
+                assert!(
+                    entrances
+                        .iter()
+                        .all(|&entrance| label(entrance) == label(entrances[0])),
+                    "{algorithm:?} with seed {seed} cuts off an entrance:\n{grid}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mazes_are_solvable() {
+        let grid_dimensions = vec2u(100, 100);
+        for algorithm in MazeAlgorithm::ALL {
+            let configuration = Configuration {
+                max_maze_dimension: usize::MAX,
+                maze_chance: 1.0,
+                maze_algorithm: algorithm,
+                ..Default::default()
+            };
+            for seed in 0..5 {
+                let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+                let mut dungeon =
+                    generate_rooms(&configuration, grid_dimensions, Some(20), &mut rng);
+                let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+                let corridors =
+                    pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+                let (mut grid, _) =
+                    make_grid(&configuration, grid_dimensions, &dungeon, &corridors)
+                        .expect("The corridors should be carved.");
+                make_mazes(&mut rng, &configuration, &mut grid, &dungeon);
+                let verification = verify(&grid, &dungeon);
+                assert!(
+                    verification.is_valid(&configuration),
+                    "{algorithm:?} mazes with seed {seed} have problems: {verification:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic_maze() {
+        // With the smallest random numbers every cell of the binary tree maze opens to the north,
+        // except for the cells of the first row, which open to the west.
+        let mut grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%%\n\
+            %_______%\n\
+            %_______%\n\
+            %_______%\n\
+            %_______%\n\
+            %_______%\n\
+            %%%%%%%%%\n",
+        );
+        let configuration = Configuration::default();
+        place_maze(
+            &mut MockMinRng,
+            &configuration,
+            MazeAlgorithm::BinaryTree,
+            &room(1, 1, 7, 5),
+            &mut grid,
+        );
+        assert_eq!(
+            grid.to_string(),
+            "\
+            %%%%%%%%%\n\
+            %_______%\n\
+            %_#_#_#_%\n\
+            %_#_#_#_%\n\
+            %_#_#_#_%\n\
+            %_#_#_#_%\n\
+            %%%%%%%%%\n",
+            "The passages should run north from a passage along the north wall."
+        );
+    }
+}
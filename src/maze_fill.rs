@@ -0,0 +1,346 @@
+use crate::grid::{Grid, Tile};
+use crate::rng::Rng;
+use crate::room::{Doorway, Dungeon};
+use crate::vec;
+
+use std::collections::HashMap;
+
+/// Parameters of the filler which grows maze passages in the empty space between the rooms and the
+/// corridors, like the "rooms and mazes" technique.
+#[derive(Clone, Copy, Debug)]
+pub struct MazeFill {
+    /// Percentage of the steps of the passages which continue in the direction of the previous
+    /// step when possible. Higher values give longer straight passages.
+    pub straightness: usize,
+    /// What proportion of the dead ends is pruned. Pruning a dead end removes the whole passage
+    /// leading to it up to the next junction, so 1 leaves no dead ends.
+    pub dead_end_pruning: f32,
+}
+
+impl Default for MazeFill {
+    fn default() -> Self {
+        Self {
+            straightness: 40,
+            dead_end_pruning: 0.5,
+        }
+    }
+}
+
+/// Marks the wall tiles which are surrounded only by other wall tiles. Passages are carved only
+/// through these, so that they stay a tile away from the rooms, the corridors and each other.
+fn free_tiles(grid: &Grid) -> Vec<bool> {
+    let mut free = vec![false; grid.tiles.len()];
+    for y in 1..grid.height().saturating_sub(1) {
+        for x in 1..grid.width - 1 {
+            free[x + y * grid.width] = grid[(x, y)] == Tile::Wall
+                && grid
+                    .neighbors8(x, y)
+                    .all(|neighbor| grid[neighbor] == Tile::Wall);
+        }
+    }
+    free
+}
+
+/// Grows a maze from every free tile with odd coordinates which is not part of a maze yet, with
+/// a randomized depth first search which moves two tiles at a time. Returns the region of every
+/// tile, usize::MAX for tiles which are not passages.
+fn grow_mazes<R: Rng>(parameters: &MazeFill, grid: &mut Grid, rng: &mut R) -> Vec<usize> {
+    let (width, height) = (grid.width, grid.height());
+    let free = free_tiles(grid);
+    let mut regions = vec![usize::MAX; grid.tiles.len()];
+    let mut region = 0;
+    let directions = [1, width, 1usize.wrapping_neg(), width.wrapping_neg()];
+
+    for y in (1..height).step_by(2) {
+        for x in (1..width).step_by(2) {
+            let start = x + y * width;
+            if !free[start] || regions[start] != usize::MAX {
+                continue;
+            }
+            grid.tiles[start] = Tile::Corridor;
+            regions[start] = region;
+            let mut stack = vec![start];
+            let mut last_direction = None;
+            while let Some(&cell) = stack.last() {
+                // Free tiles are never on the perimeter, so moving two tiles stays in the grid
+                // as long as the tile in between is free as well.
+                let options: Vec<usize> = directions
+                    .iter()
+                    .copied()
+                    .filter(|&direction| {
+                        let between = cell.wrapping_add(direction);
+                        free[between] && {
+                            let next = between.wrapping_add(direction);
+                            free[next] && regions[next] == usize::MAX
+                        }
+                    })
+                    .collect();
+                if options.is_empty() {
+                    stack.pop();
+                    last_direction = None;
+                    continue;
+                }
+                let direction = match last_direction {
+                    Some(last)
+                        if options.contains(&last)
+                            && rng.random_range(1..=100) <= parameters.straightness =>
+                    {
+                        last
+                    }
+                    _ => options[rng.random_range(0..=options.len() - 1)],
+                };
+                for tile in [
+                    cell.wrapping_add(direction),
+                    cell.wrapping_add(direction).wrapping_add(direction),
+                ] {
+                    grid.tiles[tile] = Tile::Corridor;
+                    regions[tile] = region;
+                }
+                stack.push(cell.wrapping_add(direction).wrapping_add(direction));
+                last_direction = Some(direction);
+            }
+            region += 1;
+        }
+    }
+    regions
+}
+
+/// A place where a maze can be connected to a room: the tile of the ring around the room which
+/// becomes a doorway, the wall tile outside it which becomes a passage and the tile inside the
+/// room which becomes a doorway marker.
+#[derive(Clone, Copy)]
+struct Connector {
+    room_index: usize,
+    ring: usize,
+    outside: usize,
+    inside: usize,
+}
+
+/// Finds the connectors of every maze region, grouped by the region and the room.
+fn find_connectors(
+    grid: &Grid,
+    dungeon: &Dungeon,
+    regions: &[usize],
+) -> HashMap<(usize, usize), Vec<Connector>> {
+    let width = grid.width;
+    let mut connectors: HashMap<(usize, usize), Vec<Connector>> = HashMap::new();
+    for (room_index, room) in dungeon.rooms.iter().enumerate() {
+        let bounds = room.bounds;
+        // The tiles of the sides of the room next to the ring (corners excluded) and the
+        // direction out of the room.
+        let sides = (bounds.x..bounds.x + bounds.width)
+            .flat_map(|x| {
+                [
+                    (x + bounds.y * width, width.wrapping_neg()),
+                    (x + (bounds.y + bounds.height - 1) * width, width),
+                ]
+            })
+            .chain((bounds.y..bounds.y + bounds.height).flat_map(|y| {
+                [
+                    (bounds.x + y * width, 1usize.wrapping_neg()),
+                    (bounds.x + bounds.width - 1 + y * width, 1),
+                ]
+            }));
+        for (inside, direction) in sides {
+            // The ring may be a part of the perimeter of the grid, so the tile outside it may
+            // not exist.
+            let ring = inside.wrapping_add(direction);
+            let outside = ring.wrapping_add(direction);
+            if grid.tiles[ring] != Tile::Blocker || grid.tiles.get(outside) != Some(&Tile::Wall) {
+                continue;
+            }
+            // Wall tiles are never on the perimeter, so neither is the passage.
+            let region = regions[outside.wrapping_add(direction)];
+            if region == usize::MAX {
+                continue;
+            }
+            connectors
+                .entry((region, room_index))
+                .or_default()
+                .push(Connector {
+                    room_index,
+                    ring,
+                    outside,
+                    inside,
+                });
+        }
+    }
+    connectors
+}
+
+/// Counts the neighbors of the tile one can walk to from a passage.
+fn open_neighbor_count(grid: &Grid, tile: usize) -> usize {
+    [tile + 1, tile - 1, tile + grid.width, tile - grid.width]
+        .iter()
+        .filter(|&&neighbor| matches!(grid.tiles[neighbor], Tile::Corridor | Tile::Doorway))
+        .count()
+}
+
+/// Fills the empty space between the rooms and the corridors with maze passages ("rooms and
+/// mazes"). The passages grow from every wall tile which is surrounded by other walls, which keeps
+/// them a tile away from everything else. Every maze region gets a new doorway to each room next
+/// to it and regions without any are removed. Finally, the configured proportion of the dead ends
+/// is pruned. Returns the new doorways, so they can be added to the dungeon.
+pub fn fill_with_mazes<R: Rng>(
+    parameters: &MazeFill,
+    grid: &mut Grid,
+    dungeon: &Dungeon,
+    rng: &mut R,
+) -> Vec<Doorway> {
+    let width = grid.width;
+    let mut regions = grow_mazes(parameters, grid, rng);
+
+    let mut connectors: Vec<((usize, usize), Vec<Connector>)> =
+        find_connectors(grid, dungeon, &regions)
+            .into_iter()
+            .collect();
+    // The order of a hash map changes between runs.
+    connectors.sort_by_key(|(key, _)| *key);
+    let mut connected_regions = vec![false; regions.len()];
+    let mut doorways: Vec<(Connector, bool)> = vec![];
+    for ((region, _), mut candidates) in connectors {
+        rng.shuffle(&mut candidates);
+        // Doorways of the same room must not be next to each other.
+        let Some(connector) = candidates.into_iter().find(|connector| {
+            let along_ring = if connector.outside.abs_diff(connector.ring) == 1 {
+                width
+            } else {
+                1
+            };
+            grid.tiles[connector.ring + along_ring] != Tile::Doorway
+                && grid.tiles[connector.ring - along_ring] != Tile::Doorway
+        }) else {
+            continue;
+        };
+        grid.tiles[connector.ring] = Tile::Doorway;
+        grid.tiles[connector.outside] = Tile::Corridor;
+        regions[connector.outside] = region;
+        // Place a doorway marker tile inside the room to help the maze generation procedure.
+        let marker = grid.tiles[connector.inside] == Tile::Room;
+        if marker {
+            grid.tiles[connector.inside] = Tile::Doorway;
+        }
+        connected_regions[region] = true;
+        doorways.push((connector, marker));
+    }
+
+    // Remove the regions which could not be connected to any room.
+    for (tile, region) in regions.iter_mut().enumerate() {
+        if *region != usize::MAX && !connected_regions[*region] {
+            grid.tiles[tile] = Tile::Wall;
+            *region = usize::MAX;
+        }
+    }
+
+    // Prune the dead ends, following each of them back to the next junction.
+    let mut dead_ends: Vec<usize> = (0..regions.len())
+        .filter(|&tile| regions[tile] != usize::MAX && open_neighbor_count(grid, tile) == 1)
+        .collect();
+    rng.shuffle(&mut dead_ends);
+    let prune_count =
+        (parameters.dead_end_pruning.clamp(0.0, 1.0) * dead_ends.len() as f32).round() as usize;
+    for &dead_end in &dead_ends[..prune_count] {
+        let mut current = dead_end;
+        while regions[current] != usize::MAX
+            && grid.tiles[current] == Tile::Corridor
+            && open_neighbor_count(grid, current) <= 1
+        {
+            grid.tiles[current] = Tile::Wall;
+            regions[current] = usize::MAX;
+            let Some(next) = [current + 1, current - 1, current + width, current - width]
+                .into_iter()
+                .find(|&neighbor| matches!(grid.tiles[neighbor], Tile::Corridor | Tile::Doorway))
+            else {
+                break;
+            };
+            current = next;
+        }
+    }
+
+    // Close the doorways whose passages have been pruned entirely.
+    doorways.retain(|&(connector, marker)| {
+        if grid.tiles[connector.outside] == Tile::Corridor {
+            return true;
+        }
+        grid.tiles[connector.ring] = Tile::Blocker;
+        if marker {
+            grid.tiles[connector.inside] = Tile::Room;
+        }
+        false
+    });
+
+    doorways
+        .into_iter()
+        .map(|(connector, _)| Doorway {
+            room_index: connector.room_index,
+            position: vec::vec2u(connector.ring % width, connector.ring / width),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Configuration;
+    use crate::grid::make_grid;
+    use crate::mst::pick_corridors;
+    use crate::room::generate_rooms;
+    use crate::triangulation::triangulate;
+    use crate::vec::vec2u;
+    use crate::verify::verify;
+
+    use rand::SeedableRng;
+
+    fn corridor_count(grid: &Grid) -> usize {
+        grid.tiles
+            .iter()
+            .filter(|&&tile| tile == Tile::Corridor)
+            .count()
+    }
+
+    #[test]
+    fn mazes_fill_empty_space() {
+        let configuration = Configuration::default();
+        let grid_dimensions = vec2u(80, 60);
+        for dead_end_pruning in [0.0, 0.5, 1.0] {
+            let parameters = MazeFill {
+                straightness: 40,
+                dead_end_pruning,
+            };
+            for seed in 0..5 {
+                let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+                let mut dungeon =
+                    generate_rooms(&configuration, grid_dimensions, Some(10), &mut rng);
+                let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+                let corridors =
+                    pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+                let (mut grid, _) =
+                    make_grid(&configuration, grid_dimensions, &dungeon, &corridors)
+                        .expect("The corridors should be carved.");
+                let carved_corridors = corridor_count(&grid);
+
+                let doorways = fill_with_mazes(&parameters, &mut grid, &dungeon, &mut rng);
+                dungeon.doorways.extend(doorways);
+                let verification = verify(&grid, &dungeon);
+                assert!(
+                    verification.is_valid(&configuration),
+                    "Filled grid with seed {seed} has problems: {verification:?}"
+                );
+                assert!(
+                    corridor_count(&grid) > carved_corridors,
+                    "No passages were grown with seed {seed}."
+                );
+                let dead_ends = (0..grid.tiles.len())
+                    .filter(|&tile| {
+                        grid.tiles[tile] == Tile::Corridor && open_neighbor_count(&grid, tile) < 2
+                    })
+                    .count();
+                assert_eq!(
+                    dead_ends == 0,
+                    dead_end_pruning == 1.0,
+                    "Only pruning every dead end should leave none ({dead_ends} with seed {seed})."
+                );
+            }
+        }
+    }
+}
@@ -1,10 +1,77 @@
-use crate::vec;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::vec::{self, Vector2};
 use crate::{
     Configuration,
     rng::Rng,
-    room::{Dungeon, Edges},
+    room::{Doorway, Dungeon, Edges},
 };
 
+/// Which of the edges outside the minimum spanning tree are reintroduced first when an exact
+/// number of loops is requested.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoopPreference {
+    /// Every candidate edge is equally likely to be picked.
+    Random,
+    /// Prefer the shortest edges, which make tight loops between neighboring rooms.
+    Short,
+    /// Prefer the edges which close the longest cycles in the spanning tree.
+    Long,
+}
+
+/// How the weight of an edge between two doorways is measured when picking the minimum spanning
+/// tree.
+#[derive(Clone)]
+pub enum EdgeMetric {
+    /// Straight line distance between the doorways. (Squared, since only the order matters.)
+    Euclidean,
+    /// Distance between the doorways when moving only horizontally or vertically, which is how
+    /// corridors are carved.
+    Manhattan,
+    /// Estimated cost of the A* algorithm for carving an L-shaped corridor between the doorways.
+    CarveCost,
+    /// User provided weight between the positions of two doorways.
+    Custom(Arc<dyn Fn(Vector2, Vector2) -> usize + Send + Sync>),
+}
+
+impl fmt::Debug for EdgeMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdgeMetric::Euclidean => write!(f, "Euclidean"),
+            EdgeMetric::Manhattan => write!(f, "Manhattan"),
+            EdgeMetric::CarveCost => write!(f, "CarveCost"),
+            EdgeMetric::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl EdgeMetric {
+    /// Wraps a closure which calculates the weight between the positions of two doorways.
+    pub fn custom<F: Fn(Vector2, Vector2) -> usize + Send + Sync + 'static>(weight: F) -> Self {
+        EdgeMetric::Custom(Arc::new(weight))
+    }
+
+    /// Calculates the weight of an edge between two points.
+    pub fn weight(&self, configuration: &Configuration, a: Vector2, b: Vector2) -> usize {
+        let distance = a - b;
+        let manhattan = (distance.x.unsigned_abs() + distance.y.unsigned_abs()) as usize;
+        match self {
+            EdgeMetric::Euclidean => distance.length_sqr() as usize,
+            EdgeMetric::Manhattan => manhattan,
+            EdgeMetric::CarveCost => {
+                // The first step and the turn (if there is one) are standard steps and all the
+                // other steps continue in a straight line.
+                let standard_steps =
+                    (1 + usize::from(distance.x != 0 && distance.y != 0)).min(manhattan);
+                standard_steps * configuration.standard_cost
+                    + (manhattan - standard_steps) * configuration.straight_cost
+            }
+            EdgeMetric::Custom(weight) => weight(a, b),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct DisjointSet {
     parent: Vec<usize>,
@@ -77,8 +144,94 @@ pub fn minimum_spanning_tree<P: Clone + Into<vec::Vector2>>(
     edge_indices
 }
 
+/// The minimum and maximum number of corridors of every room which doorways refer to. Rooms which
+/// match an archetype use its limits and all other rooms use the global limits.
+fn degree_limits(configuration: &Configuration, dungeon: &Dungeon) -> Vec<(usize, usize)> {
+    let room_count = dungeon
+        .doorways
+        .iter()
+        .map(|doorway| doorway.room_index + 1)
+        .max()
+        .unwrap_or(0)
+        .max(dungeon.rooms.len());
+    (0..room_count)
+        .map(|room_index| {
+            dungeon
+                .rooms
+                .get(room_index)
+                .and_then(|room| {
+                    configuration
+                        .room_archetypes
+                        .iter()
+                        .find(|archetype| archetype.matches(room))
+                })
+                .map_or(configuration.room_degree, |archetype| archetype.degree)
+        })
+        .collect()
+}
+
+/// Kruskal's algorithm on edges which are already sorted by weight, which skips edges that would
+/// give a room more corridors than its maximum. Afterwards the skipped edges which connect
+/// separate components are added anyway, so that every room stays connected. Returns the sorted
+/// indices of the tree edges.
+fn degree_constrained_spanning_tree(
+    doorways: &[Doorway],
+    edges: &Edges,
+    limits: &[(usize, usize)],
+    degree: &mut [usize],
+) -> Vec<usize> {
+    let mut edge_indices: Vec<usize> = vec![];
+    let mut skipped_edges: Vec<usize> = vec![];
+
+    let mut disjoint_set = DisjointSet::new(doorways.len());
+    for (edge_index, edge) in edges.iter().enumerate() {
+        if disjoint_set.find_set(edge.0) == disjoint_set.find_set(edge.1) {
+            continue;
+        }
+        let room_a = doorways[edge.0].room_index;
+        let room_b = doorways[edge.1].room_index;
+        if room_a != room_b {
+            if degree[room_a] >= limits[room_a].1 || degree[room_b] >= limits[room_b].1 {
+                skipped_edges.push(edge_index);
+                continue;
+            }
+            degree[room_a] += 1;
+            degree[room_b] += 1;
+        }
+        edge_indices.push(edge_index);
+        disjoint_set.union_sets(edge.0, edge.1);
+    }
+
+    if skipped_edges.is_empty() {
+        return edge_indices;
+    }
+
+    // The maximum degree could not be respected without disconnecting the rooms.
+    for edge_index in skipped_edges {
+        let edge = edges[edge_index];
+        if disjoint_set.find_set(edge.0) == disjoint_set.find_set(edge.1) {
+            continue;
+        }
+        degree[doorways[edge.0].room_index] += 1;
+        degree[doorways[edge.1].room_index] += 1;
+        edge_indices.push(edge_index);
+        disjoint_set.union_sets(edge.0, edge.1);
+    }
+    edge_indices.sort();
+
+    edge_indices
+}
+
 /// Picks the final corridors from the triangulation by creating a MST and reintroducing some of
-/// the edges of the triangulation back.
+/// the edges of the triangulation back. The reintroduced edges are either picked at random with
+/// the configured density or, when a loop count is configured, in the order of the configured
+/// loop preference.
+///
+/// The number of corridors of each room is kept within the configured limits. The tree exceeds the
+/// maximum only when there is no other way to connect all the rooms. Before the random
+/// reintroduction, the shortest edges of rooms with fewer corridors than the minimum are
+/// reintroduced, as long as the triangulation has such edges. These count towards the loop count,
+/// which they may exceed on their own.
 pub fn pick_corridors<R: Rng>(
     configuration: &Configuration,
     dungeon: &Dungeon,
@@ -87,7 +240,25 @@ pub fn pick_corridors<R: Rng>(
 ) -> Edges {
     let Dungeon { rooms: _, doorways } = dungeon;
 
-    let tree = minimum_spanning_tree(doorways, triangulation);
+    // The weights are kept in hundredths, so that the jitter changes small weights too.
+    let jitter = configuration.edge_weight_jitter;
+    triangulation.sort_by_cached_key(|edge| {
+        let weight = configuration.edge_metric.weight(
+            configuration,
+            doorways[edge.0].position,
+            doorways[edge.1].position,
+        );
+        let percent = if jitter == 0 {
+            100
+        } else {
+            100 + rng.random_range(0..=jitter)
+        };
+        weight.saturating_mul(percent)
+    });
+
+    let limits = degree_limits(configuration, dungeon);
+    let mut degree = vec![0; limits.len()];
+    let tree = degree_constrained_spanning_tree(doorways, triangulation, &limits, &mut degree);
 
     // Find the edges which are not part of the minimum spanning tree.
     // Since the indices in the tree array are sorted we can do this in O(n) as such.
@@ -106,13 +277,80 @@ pub fn pick_corridors<R: Rng>(
     // Add edges from the minimum spanning tree which connect
     // doorways of different rooms to the corridor array.
     let mut edge: (usize, usize);
-    for edge_index in tree {
+    for &edge_index in &tree {
         edge = triangulation[edge_index];
         if doorways[edge.0].room_index != doorways[edge.1].room_index {
             corridors.push(edge);
         }
     }
 
+    // Edges which connect doorways of the same room cannot become corridors.
+    residual_edges.retain(|&edge_index| {
+        let edge = triangulation[edge_index];
+        doorways[edge.0].room_index != doorways[edge.1].room_index
+    });
+
+    let rooms_of =
+        |edge: (usize, usize)| (doorways[edge.0].room_index, doorways[edge.1].room_index);
+    let fits = |degree: &[usize], (room_a, room_b): (usize, usize)| {
+        degree[room_a] < limits[room_a].1 && degree[room_b] < limits[room_b].1
+    };
+
+    // Reintroduce the shortest edges of rooms which have too few corridors.
+    let tree_corridor_count = corridors.len();
+    residual_edges.retain(|&edge_index| {
+        let edge = triangulation[edge_index];
+        let (room_a, room_b) = rooms_of(edge);
+        if (degree[room_a] < limits[room_a].0 || degree[room_b] < limits[room_b].0)
+            && fits(&degree, (room_a, room_b))
+        {
+            degree[room_a] += 1;
+            degree[room_b] += 1;
+            corridors.push(edge);
+            return false;
+        }
+        true
+    });
+
+    if let Some((min_count, max_count)) = configuration.reintroduced_corridor_count {
+        // The edges reintroduced for the minimum degrees already close loops.
+        let mut count = rng
+            .random_range(min_count..=max_count)
+            .saturating_sub(corridors.len() - tree_corridor_count)
+            .min(residual_edges.len());
+        match configuration.loop_preference {
+            LoopPreference::Random => {
+                // Fisher-Yates shuffle which goes through the mockable random number generator.
+                for index in (1..residual_edges.len()).rev() {
+                    residual_edges.swap(index, rng.random_range(0..=index));
+                }
+            }
+            // The triangulation has already been sorted by edge weight.
+            LoopPreference::Short => {}
+            LoopPreference::Long => {
+                let lengths = loop_lengths(doorways, triangulation, &tree, &residual_edges);
+                let mut order: Vec<usize> = (0..residual_edges.len()).collect();
+                order.sort_by_key(|&index| std::cmp::Reverse(lengths[index]));
+                residual_edges = order.iter().map(|&index| residual_edges[index]).collect();
+            }
+        }
+        for residual_edge_index in residual_edges {
+            if count == 0 {
+                break;
+            }
+            edge = triangulation[residual_edge_index];
+            let (room_a, room_b) = rooms_of(edge);
+            if !fits(&degree, (room_a, room_b)) {
+                continue;
+            }
+            degree[room_a] += 1;
+            degree[room_b] += 1;
+            corridors.push(edge);
+            count -= 1;
+        }
+        return corridors;
+    }
+
     // Even though the rand library provides a facility to shuffle a sequence,
     // that function calls different methods on the random number generators,
     // however, for the purposes of testing I need to be able to mock them.
@@ -121,11 +359,14 @@ pub fn pick_corridors<R: Rng>(
     // doorways of different rooms to the corridor array.
     for residual_edge_index in residual_edges {
         edge = triangulation[residual_edge_index];
-        if doorways[edge.0].room_index == doorways[edge.1].room_index {
+        let (room_a, room_b) = rooms_of(edge);
+        if !fits(&degree, (room_a, room_b)) {
             continue;
         }
         let filter_number = rng.random_range(1..=configuration.reintroduced_corridor_density.1);
         if filter_number <= configuration.reintroduced_corridor_density.0 {
+            degree[room_a] += 1;
+            degree[room_b] += 1;
             corridors.push(edge);
         }
     }
@@ -133,11 +374,80 @@ pub fn pick_corridors<R: Rng>(
     corridors
 }
 
+/// Calculates the length of the loop each of the given edges would close if it were added to the
+/// spanning tree. The length is measured in corridors i.e. tree edges between doorways of the same
+/// room do not count.
+fn loop_lengths(
+    doorways: &[Doorway],
+    triangulation: &Edges,
+    tree: &[usize],
+    edge_indices: &[usize],
+) -> Vec<usize> {
+    let mut neighbors: Vec<Vec<usize>> = vec![vec![]; doorways.len()];
+    for &edge_index in tree {
+        let edge = triangulation[edge_index];
+        neighbors[edge.0].push(edge.1);
+        neighbors[edge.1].push(edge.0);
+    }
+
+    // Root every tree of the forest and calculate the depth of each doorway both in edges and in
+    // corridors.
+    let mut parent: Vec<usize> = (0..doorways.len()).collect();
+    let mut depth = vec![0; doorways.len()];
+    let mut corridor_depth = vec![0; doorways.len()];
+    let mut visited = vec![false; doorways.len()];
+    let mut stack: Vec<usize> = vec![];
+    for root in 0..doorways.len() {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        stack.push(root);
+        while let Some(current) = stack.pop() {
+            for &neighbor in &neighbors[current] {
+                if visited[neighbor] {
+                    continue;
+                }
+                visited[neighbor] = true;
+                parent[neighbor] = current;
+                depth[neighbor] = depth[current] + 1;
+                corridor_depth[neighbor] = corridor_depth[current]
+                    + usize::from(doorways[neighbor].room_index != doorways[current].room_index);
+                stack.push(neighbor);
+            }
+        }
+    }
+
+    edge_indices
+        .iter()
+        .map(|&edge_index| {
+            let (mut a, mut b) = triangulation[edge_index];
+            let corridors = corridor_depth[a] + corridor_depth[b];
+            while depth[a] > depth[b] {
+                a = parent[a];
+            }
+            while depth[b] > depth[a] {
+                b = parent[b];
+            }
+            while a != b && parent[a] != a {
+                a = parent[a];
+                b = parent[b];
+            }
+            if a != b {
+                // The doorways are in different trees so the edge does not close a loop.
+                return 0;
+            }
+            corridors - 2 * corridor_depth[a] + 1
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::mock::MockRng;
-    use crate::mock::doorway;
+    use crate::mock::{MockMaxRng, MockRng};
+    use crate::mock::{doorway, room};
+    use crate::room::RoomArchetype;
     use crate::vec::vec2u;
 
     #[test]
@@ -409,4 +719,341 @@ mod test {
             "There should be exactly 5 edges in the picked corridors."
         );
     }
+
+    fn chain_dungeon() -> (Dungeon, Edges) {
+        // The minimum spanning tree is the chain 0-1-2-3. Edge (0, 2) closes a loop of three
+        // corridors and edge (0, 3) closes a loop of four corridors.
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![
+                doorway(0, 0, 0),
+                doorway(2, 0, 1),
+                doorway(4, 0, 2),
+                doorway(6, 0, 3),
+            ],
+        };
+        let triangulation = vec![(0, 1), (1, 2), (2, 3), (0, 2), (0, 3)];
+        (dungeon, triangulation)
+    }
+
+    #[test]
+    fn loop_count_prefers_short_edges() {
+        let configuration = Configuration {
+            reintroduced_corridor_count: Some((1, 1)),
+            loop_preference: LoopPreference::Short,
+            ..Default::default()
+        };
+        let (dungeon, mut triangulation) = chain_dungeon();
+        let corridors = pick_corridors(
+            &configuration,
+            &dungeon,
+            &mut triangulation,
+            &mut MockMaxRng,
+        );
+
+        assert_eq!(
+            &corridors,
+            &[(0, 1), (1, 2), (2, 3), (0, 2)],
+            "The shortest edge outside the tree should be the only one reintroduced."
+        );
+    }
+
+    #[test]
+    fn loop_count_prefers_long_loops() {
+        let configuration = Configuration {
+            reintroduced_corridor_count: Some((1, 1)),
+            loop_preference: LoopPreference::Long,
+            ..Default::default()
+        };
+        let (dungeon, mut triangulation) = chain_dungeon();
+        let corridors = pick_corridors(
+            &configuration,
+            &dungeon,
+            &mut triangulation,
+            &mut MockMaxRng,
+        );
+
+        assert_eq!(
+            &corridors,
+            &[(0, 1), (1, 2), (2, 3), (0, 3)],
+            "The edge which closes the longest loop should be the only one reintroduced."
+        );
+    }
+
+    #[test]
+    fn loop_count_is_limited_by_candidates() {
+        let configuration = Configuration {
+            reintroduced_corridor_count: Some((0, 10)),
+            loop_preference: LoopPreference::Random,
+            ..Default::default()
+        };
+        let (dungeon, mut triangulation) = chain_dungeon();
+        let corridors = pick_corridors(
+            &configuration,
+            &dungeon,
+            &mut triangulation,
+            &mut MockMaxRng,
+        );
+        assert_eq!(
+            corridors.len(),
+            5,
+            "All edges should be picked when more loops are requested than are available."
+        );
+
+        // The first number picks the loop count and the second shuffles the candidates.
+        let mut mock_rng = MockRng::new(vec![1, 0]);
+        let (dungeon, mut triangulation) = chain_dungeon();
+        let corridors = pick_corridors(&configuration, &dungeon, &mut triangulation, &mut mock_rng);
+        assert_eq!(
+            &corridors,
+            &[(0, 1), (1, 2), (2, 3), (0, 3)],
+            "Exactly one shuffled edge should be reintroduced."
+        );
+    }
+
+    #[test]
+    fn loop_count_includes_minimum_degree_edges() {
+        // The end rooms of the chain 0-1-2-3-4 get the edges (0, 2) and (2, 4) to reach their
+        // minimum degree, which leaves (1, 3) as the only candidate.
+        let configuration = Configuration {
+            reintroduced_corridor_count: Some((2, 2)),
+            loop_preference: LoopPreference::Short,
+            room_degree: (2, usize::MAX),
+            ..Default::default()
+        };
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![
+                doorway(0, 0, 0),
+                doorway(2, 0, 1),
+                doorway(4, 0, 2),
+                doorway(6, 0, 3),
+                doorway(8, 0, 4),
+            ],
+        };
+        let mut triangulation = vec![(0, 1), (1, 2), (2, 3), (3, 4), (0, 2), (1, 3), (2, 4)];
+        let corridors = pick_corridors(
+            &configuration,
+            &dungeon,
+            &mut triangulation,
+            &mut MockMaxRng,
+        );
+        assert_eq!(
+            &corridors,
+            &[(0, 1), (1, 2), (2, 3), (3, 4), (0, 2), (2, 4)],
+            "The edges reintroduced for the minimum degree should be the requested loops."
+        );
+    }
+
+    #[test]
+    fn loop_length_ignores_same_room_edges() {
+        // Doorways 1 and 2 belong to the same room so the loop closed by (0, 3) is made of three
+        // corridors: 0-1, 2-3 and the edge itself.
+        let doorways = [
+            doorway(0, 0, 0),
+            doorway(2, 0, 1),
+            doorway(4, 0, 1),
+            doorway(6, 0, 2),
+        ];
+        let triangulation = vec![(0, 1), (1, 2), (2, 3), (0, 3)];
+        let lengths = loop_lengths(&doorways, &triangulation, &[0, 1, 2], &[3]);
+        assert_eq!(&lengths, &[3], "Loop length is incorrect.");
+    }
+
+    fn metric_dungeon() -> (Dungeon, Edges) {
+        // Euclidean distance prefers the edges (1, 2) and (0, 1) while Manhattan distance prefers
+        // the edges (0, 2) and (1, 2).
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![doorway(0, 0, 0), doorway(3, 3, 1), doorway(5, 0, 2)],
+        };
+        (dungeon, vec![(0, 1), (0, 2), (1, 2)])
+    }
+
+    fn pick_tree(configuration: Configuration, rng: &mut impl Rng) -> Edges {
+        let configuration = Configuration {
+            reintroduced_corridor_density: (0, 1),
+            ..configuration
+        };
+        let (dungeon, mut triangulation) = metric_dungeon();
+        let mut corridors = pick_corridors(&configuration, &dungeon, &mut triangulation, rng);
+        corridors.sort();
+        corridors
+    }
+
+    #[test]
+    fn edge_metrics() {
+        let euclidean = pick_tree(Configuration::default(), &mut MockMaxRng);
+        assert_eq!(
+            &euclidean,
+            &[(0, 1), (1, 2)],
+            "Euclidean tree is incorrect."
+        );
+
+        let manhattan = pick_tree(
+            Configuration {
+                edge_metric: EdgeMetric::Manhattan,
+                ..Default::default()
+            },
+            &mut MockMaxRng,
+        );
+        assert_eq!(
+            &manhattan,
+            &[(0, 2), (1, 2)],
+            "Manhattan tree is incorrect."
+        );
+
+        // Make the edge (1, 2) the most expensive one. The closure may capture its environment.
+        let scale = 2;
+        let custom = pick_tree(
+            Configuration {
+                edge_metric: EdgeMetric::custom(move |a, b| (a.x + b.x) as usize * scale),
+                ..Default::default()
+            },
+            &mut MockMaxRng,
+        );
+        assert_eq!(&custom, &[(0, 1), (0, 2)], "Custom tree is incorrect.");
+    }
+
+    #[test]
+    fn carve_cost_metric() {
+        let configuration = Configuration::default();
+        let metric = EdgeMetric::CarveCost;
+        assert_eq!(
+            metric.weight(&configuration, vec2u(1, 1), vec2u(5, 1)),
+            configuration.standard_cost + 3 * configuration.straight_cost,
+            "A straight corridor should have a single standard step."
+        );
+        assert_eq!(
+            metric.weight(&configuration, vec2u(1, 1), vec2u(5, 4)),
+            2 * configuration.standard_cost + 5 * configuration.straight_cost,
+            "An L-shaped corridor should have two standard steps."
+        );
+        assert_eq!(
+            metric.weight(&configuration, vec2u(1, 1), vec2u(1, 1)),
+            0,
+            "The weight between a point and itself should be zero."
+        );
+    }
+
+    #[test]
+    fn jittered_edge_weights() {
+        // The Manhattan weights are 6, 5 and 5 in the order of the triangulation. Doubling the
+        // weight of (0, 2) makes the tree match the Euclidean one. The last number is used for the
+        // reintroduction of the residual edge.
+        let configuration = Configuration {
+            edge_metric: EdgeMetric::Manhattan,
+            edge_weight_jitter: 100,
+            ..Default::default()
+        };
+        let mut mock_rng = MockRng::new(vec![0, 100, 0, 1]);
+        let corridors = pick_tree(configuration, &mut mock_rng);
+        assert_eq!(&corridors, &[(0, 1), (1, 2)], "Jittered tree is incorrect.");
+
+        // With equal weights the tree would keep the first two edges, so even a jitter of less
+        // than a whole weight should move the edge (0, 1) to the end.
+        let configuration = Configuration {
+            edge_metric: EdgeMetric::custom(|_, _| 1),
+            edge_weight_jitter: 50,
+            ..Default::default()
+        };
+        let mut mock_rng = MockRng::new(vec![50, 0, 0, 1]);
+        let corridors = pick_tree(configuration, &mut mock_rng);
+        assert_eq!(
+            &corridors,
+            &[(0, 2), (1, 2)],
+            "Jitter should change small weights."
+        );
+    }
+
+    #[test]
+    fn maximum_room_degree() {
+        // Without limits the tree would connect room 1 to all the other rooms.
+        let configuration = Configuration {
+            reintroduced_corridor_density: (1, 1),
+            room_degree: (0, 2),
+            ..Default::default()
+        };
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![
+                doorway(0, 0, 0),
+                doorway(2, 0, 1),
+                doorway(4, 0, 2),
+                doorway(2, 2, 3),
+            ],
+        };
+        let mut triangulation = vec![(0, 1), (1, 2), (1, 3), (0, 3), (2, 3)];
+        let mut corridors = pick_corridors(
+            &configuration,
+            &dungeon,
+            &mut triangulation,
+            &mut MockMaxRng,
+        );
+        corridors.sort();
+
+        // The edge (2, 3) is reintroduced since neither room is at its maximum.
+        assert_eq!(
+            &corridors,
+            &[(0, 1), (0, 3), (1, 2), (2, 3)],
+            "No room should have more than two corridors."
+        );
+    }
+
+    #[test]
+    fn maximum_room_degree_keeps_connectivity() {
+        let configuration = Configuration {
+            room_degree: (0, 1),
+            ..Default::default()
+        };
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![doorway(0, 0, 0), doorway(2, 0, 1), doorway(4, 0, 2)],
+        };
+        let mut triangulation = vec![(0, 1), (1, 2)];
+        let corridors = pick_corridors(
+            &configuration,
+            &dungeon,
+            &mut triangulation,
+            &mut MockMaxRng,
+        );
+        assert_eq!(
+            corridors.len(),
+            2,
+            "The middle room should exceed its maximum to keep the rooms connected."
+        );
+    }
+
+    #[test]
+    fn minimum_room_degree_of_archetype() {
+        // Only the first room is big enough to belong to the archetype.
+        let configuration = Configuration {
+            reintroduced_corridor_density: (0, 1),
+            room_archetypes: vec![RoomArchetype {
+                min_dimension: 7,
+                max_dimension: 7,
+                degree: (2, usize::MAX),
+                maze_algorithm: None,
+            }],
+            ..Default::default()
+        };
+        let (mut dungeon, mut triangulation) = chain_dungeon();
+        dungeon.rooms = vec![
+            room(10, 10, 7, 7),
+            room(20, 10, 5, 5),
+            room(30, 10, 5, 5),
+            room(40, 10, 5, 5),
+        ];
+        let corridors = pick_corridors(
+            &configuration,
+            &dungeon,
+            &mut triangulation,
+            &mut MockMaxRng,
+        );
+        assert_eq!(
+            &corridors,
+            &[(0, 1), (1, 2), (2, 3), (0, 2)],
+            "The shortest edge of the first room should be reintroduced."
+        );
+    }
 }
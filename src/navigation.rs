@@ -0,0 +1,451 @@
+use crate::binary_heap::IndexedHeap;
+use crate::grid::{Grid, Tile};
+use crate::vec::{self, Vector2};
+
+/// Which neighbors of a tile can be reached in a single step.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Connectivity {
+    /// Only horizontal and vertical steps.
+    Four,
+    /// Horizontal, vertical and diagonal steps.
+    Eight,
+}
+
+/// The default cost of entering a tile. Rooms, doorways and corridors can be walked on and all
+/// other tiles cannot.
+pub fn default_tile_cost(tile: Tile) -> Option<usize> {
+    match tile {
+        Tile::Room | Tile::Doorway | Tile::Corridor => Some(1),
+        _ => None,
+    }
+}
+
+/// Options for pathfinding through a generated grid.
+#[derive(Clone, Copy, Debug)]
+pub struct NavigationOptions {
+    pub connectivity: Connectivity,
+    /// Cost of entering a tile of the given type or None if the tile cannot be walked on. The
+    /// cost is multiplied by the cost of the step.
+    pub tile_cost: fn(Tile) -> Option<usize>,
+    /// Cost of a horizontal or vertical step.
+    pub straight_step: usize,
+    /// Cost of a diagonal step. Used only with eight-connectivity.
+    pub diagonal_step: usize,
+    /// Whether a diagonal step may pass by the corner of a tile which cannot be walked on.
+    pub cut_corners: bool,
+}
+
+impl Default for NavigationOptions {
+    fn default() -> Self {
+        Self {
+            connectivity: Connectivity::Four,
+            tile_cost: default_tile_cost,
+            straight_step: 10,
+            diagonal_step: 14,
+            cut_corners: false,
+        }
+    }
+}
+
+/// A path through the grid given in coordinates from the start to the end (both included).
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub positions: Vec<Vector2>,
+    pub cost: usize,
+}
+
+/// Finds paths and distances through generated grids. Owns its buffers so that it can be used
+/// many times (e.g. every frame of a game) without allocating. Like the pathfinder which carves
+/// corridors, tiles remember the search in which they were last written, so each search costs
+/// only as much as the tiles it explores.
+pub struct Navigator {
+    pub options: NavigationOptions,
+    open_set: IndexedHeap<usize>,
+    costs: Vec<usize>,
+    parent: Vec<usize>,
+    generations: Vec<u32>,
+    generation: u32,
+}
+
+impl Navigator {
+    pub fn new(options: NavigationOptions) -> Self {
+        Self {
+            options,
+            open_set: IndexedHeap::with_capacity(0),
+            costs: vec![],
+            parent: vec![],
+            generations: vec![],
+            generation: 0,
+        }
+    }
+
+    fn cost(&self, tile: usize) -> usize {
+        if self.generations[tile] == self.generation {
+            self.costs[tile]
+        } else {
+            usize::MAX
+        }
+    }
+
+    /// Starts a new search, invalidating the values of all the previous ones.
+    fn next_generation(&mut self, tile_count: usize) {
+        if self.generations.len() < tile_count {
+            self.costs.resize(tile_count, 0);
+            self.parent.resize(tile_count, 0);
+            self.generations.resize(tile_count, 0);
+            self.open_set.reserve_handles(tile_count);
+        }
+        if self.generation == u32::MAX {
+            self.generations.fill(0);
+            self.generation = 0;
+        }
+        self.generation += 1;
+        self.open_set.clear();
+    }
+
+    /// Converts a position into a tile index if it is in the grid and can be walked on.
+    fn walkable_index(&self, grid: &Grid, position: Vector2) -> Option<usize> {
+        let height = grid.height();
+        if position.x < 0
+            || position.y < 0
+            || position.x as usize >= grid.width
+            || position.y as usize >= height
+        {
+            return None;
+        }
+        let index = vec::to_index(position, grid.width);
+        (self.options.tile_cost)(grid.tiles[index]).map(|_| index)
+    }
+
+    /// The lowest possible cost of getting from one tile to another, used as the heuristic of A*.
+    fn estimate(&self, width: usize, min_tile_cost: usize, a: usize, b: usize) -> usize {
+        let dx = (a % width).abs_diff(b % width);
+        let dy = (a / width).abs_diff(b / width);
+        let straight = self.options.straight_step;
+        let estimate = match self.options.connectivity {
+            Connectivity::Four => (dx + dy) * straight,
+            Connectivity::Eight => {
+                // A diagonal step is never worse than two straight steps and when diagonal steps
+                // are cheaper, a straight stretch may be walked by zigzagging.
+                let diagonal = self.options.diagonal_step.min(2 * straight);
+                dx.abs_diff(dy) * straight.min(diagonal) + dx.min(dy) * diagonal
+            }
+        };
+        estimate * min_tile_cost
+    }
+
+    /// Runs Dijkstra's algorithm from the sources or, when there is a target, A*. Returns whether
+    /// the target was reached.
+    fn search(&mut self, grid: &Grid, sources: &[usize], target: Option<usize>) -> bool {
+        let width = grid.width;
+        let height = grid.height();
+        let tile_cost = self.options.tile_cost;
+        let min_tile_cost = Tile::ALL
+            .iter()
+            .filter_map(|&tile| tile_cost(tile))
+            .min()
+            .unwrap_or(0);
+        let heuristic = |navigator: &Self, tile: usize| {
+            target.map_or(0, |target| {
+                navigator.estimate(width, min_tile_cost, tile, target)
+            })
+        };
+
+        self.next_generation(grid.tiles.len());
+        for &source in sources {
+            self.generations[source] = self.generation;
+            self.costs[source] = 0;
+            self.parent[source] = source;
+            // Sources may be given more than once.
+            if !self.open_set.contains(source) {
+                self.open_set.insert(heuristic(self, source), source);
+            }
+        }
+
+        let offsets: &[(i32, i32)] = match self.options.connectivity {
+            Connectivity::Four => &[(0, 1), (0, -1), (1, 0), (-1, 0)],
+            Connectivity::Eight => &[
+                (0, 1),
+                (0, -1),
+                (1, 0),
+                (-1, 0),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1),
+            ],
+        };
+        let walkable = |x: i32, y: i32| {
+            x >= 0
+                && y >= 0
+                && (x as usize) < width
+                && (y as usize) < height
+                && tile_cost(grid.tiles[x as usize + y as usize * width]).is_some()
+        };
+
+        while let Some((_, current)) = self.open_set.extract_min() {
+            let cost = self.cost(current);
+            if Some(current) == target {
+                return true;
+            }
+
+            let x = (current % width) as i32;
+            let y = (current / width) as i32;
+            for &(dx, dy) in offsets {
+                if !walkable(x + dx, y + dy) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                if diagonal
+                    && !self.options.cut_corners
+                    && !(walkable(x + dx, y) && walkable(x, y + dy))
+                {
+                    continue;
+                }
+
+                let neighbor = (x + dx) as usize + (y + dy) as usize * width;
+                let step = if diagonal {
+                    self.options.diagonal_step
+                } else {
+                    self.options.straight_step
+                };
+                // The tile is walkable so it has a cost.
+                let tentative_cost = cost + step * tile_cost(grid.tiles[neighbor]).unwrap_or(0);
+                if tentative_cost < self.cost(neighbor) {
+                    self.generations[neighbor] = self.generation;
+                    self.costs[neighbor] = tentative_cost;
+                    self.parent[neighbor] = current;
+                    let f_cost = tentative_cost + heuristic(self, neighbor);
+                    if self.open_set.contains(neighbor) {
+                        self.open_set.decrease_key(neighbor, f_cost);
+                    } else {
+                        self.open_set.insert(f_cost, neighbor);
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Finds the cheapest path between two positions with A*. Returns None if either position is
+    /// outside the grid or cannot be walked on or if there is no path between them.
+    pub fn find_path(&mut self, grid: &Grid, start: Vector2, end: Vector2) -> Option<Path> {
+        let start = self.walkable_index(grid, start)?;
+        let end = self.walkable_index(grid, end)?;
+        if !self.search(grid, &[start], Some(end)) {
+            return None;
+        }
+
+        let mut positions = vec![];
+        let mut current = end;
+        loop {
+            positions.push(vec::vec2u(current % grid.width, current / grid.width));
+            if current == start {
+                break;
+            }
+            current = self.parent[current];
+        }
+        positions.reverse();
+
+        Some(Path {
+            positions,
+            cost: self.costs[end],
+        })
+    }
+
+    /// Calculates the cost of the cheapest path from the closest source to every tile with
+    /// Dijkstra's algorithm (also known as a Dijkstra map). Tiles which cannot be reached are
+    /// None. Sources outside the grid or which cannot be walked on are ignored.
+    pub fn distance_map(&mut self, grid: &Grid, sources: &[Vector2]) -> Vec<Option<usize>> {
+        let sources: Vec<usize> = sources
+            .iter()
+            .filter_map(|&source| self.walkable_index(grid, source))
+            .collect();
+        self.search(grid, &sources, None);
+        (0..grid.tiles.len())
+            .map(|tile| {
+                let cost = self.cost(tile);
+                (cost != usize::MAX).then_some(cost)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec::{to_index, vec2, vec2u};
+
+    fn room_with_pillar() -> Grid {
+        Grid::parse_lenient(
+            "\
+            %%%%%%%\n\
+            %_____%\n\
+            %__#__%\n\
+            %_____%\n\
+            %%%%%%%\n",
+        )
+    }
+
+    #[test]
+    fn four_connected_path() {
+        let grid = room_with_pillar();
+        let mut navigator = Navigator::new(NavigationOptions::default());
+        let path = navigator
+            .find_path(&grid, vec2u(1, 2), vec2u(5, 2))
+            .expect("There should be a path.");
+        assert_eq!(path.positions.len(), 7, "Path length is incorrect.");
+        assert_eq!(path.cost, 60, "Path cost is incorrect.");
+        assert_eq!(
+            path.positions[0],
+            vec2u(1, 2),
+            "The path should begin at the start."
+        );
+        assert_eq!(
+            path.positions[6],
+            vec2u(5, 2),
+            "The path should end at the end."
+        );
+        for pair in path.positions.windows(2) {
+            let step = pair[1] - pair[0];
+            assert_eq!(
+                step.x.abs() + step.y.abs(),
+                1,
+                "Steps should be horizontal or vertical."
+            );
+        }
+    }
+
+    #[test]
+    fn eight_connected_path() {
+        let grid = room_with_pillar();
+        let mut navigator = Navigator::new(NavigationOptions {
+            connectivity: Connectivity::Eight,
+            ..Default::default()
+        });
+        let path = navigator
+            .find_path(&grid, vec2u(1, 1), vec2u(5, 3))
+            .expect("There should be a path.");
+        assert_eq!(path.cost, 54, "Path cost is incorrect.");
+
+        // Without cutting corners the diagonal steps next to the pillar are not allowed.
+        let path = navigator
+            .find_path(&grid, vec2u(2, 1), vec2u(4, 3))
+            .expect("There should be a path.");
+        assert_eq!(path.cost, 40, "Path cost is incorrect.");
+
+        navigator.options.cut_corners = true;
+        let path = navigator
+            .find_path(&grid, vec2u(2, 1), vec2u(4, 3))
+            .expect("There should be a path.");
+        assert_eq!(path.cost, 34, "Path cost is incorrect.");
+    }
+
+    #[test]
+    fn cheap_diagonal_steps() {
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%\n\
+            %_%%__%\n\
+            %_____%\n\
+            %_____%\n\
+            %%%%%%%\n",
+        );
+        // Zigzagging is cheaper than walking straight, so the heuristic must not count the
+        // straight part of the distance with straight steps or the path is not the cheapest.
+        let mut navigator = Navigator::new(NavigationOptions {
+            connectivity: Connectivity::Eight,
+            straight_step: 10,
+            diagonal_step: 6,
+            ..Default::default()
+        });
+        for cut_corners in [false, true] {
+            navigator.options.cut_corners = cut_corners;
+            let path = navigator
+                .find_path(&grid, vec2u(1, 1), vec2u(4, 1))
+                .expect("There should be a path.");
+            let distances = navigator.distance_map(&grid, &[vec2u(1, 1)]);
+            assert_eq!(
+                Some(path.cost),
+                distances[to_index(vec2u(4, 1), grid.width)],
+                "The path should be the cheapest one."
+            );
+        }
+    }
+
+    #[test]
+    fn custom_tile_costs() {
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%\n\
+            %d___d%\n\
+            %ccccc%\n\
+            %%%%%%%\n",
+        );
+        // Rooms are expensive so the path should go through the corridor.
+        let mut navigator = Navigator::new(NavigationOptions {
+            tile_cost: |tile| match tile {
+                Tile::Room => Some(5),
+                Tile::Doorway | Tile::Corridor => Some(1),
+                _ => None,
+            },
+            straight_step: 1,
+            ..Default::default()
+        });
+        let path = navigator
+            .find_path(&grid, vec2u(1, 1), vec2u(5, 1))
+            .expect("There should be a path.");
+        assert_eq!(path.cost, 6, "Path cost is incorrect.");
+        assert_eq!(path.positions.len(), 7, "Path length is incorrect.");
+    }
+
+    #[test]
+    fn unreachable_and_invalid_positions() {
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%\n\
+            %__%__%\n\
+            %%%%%%%\n",
+        );
+        let mut navigator = Navigator::new(NavigationOptions::default());
+        assert!(
+            navigator
+                .find_path(&grid, vec2u(1, 1), vec2u(5, 1))
+                .is_none(),
+            "There should be no path through the wall."
+        );
+        assert!(
+            navigator
+                .find_path(&grid, vec2u(1, 1), vec2u(3, 1))
+                .is_none(),
+            "The end cannot be walked on."
+        );
+        assert!(
+            navigator
+                .find_path(&grid, vec2(-1, 1), vec2u(2, 1))
+                .is_none(),
+            "The start is outside the grid."
+        );
+    }
+
+    #[test]
+    fn dijkstra_map() {
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%\n\
+            %__%__%\n\
+            %%%%%%%\n",
+        );
+        let mut navigator = Navigator::new(NavigationOptions {
+            straight_step: 1,
+            ..Default::default()
+        });
+        let distances = navigator.distance_map(&grid, &[vec2u(1, 1), vec2u(5, 1)]);
+        assert_eq!(
+            &distances[7..14],
+            &[None, Some(0), Some(1), None, Some(1), Some(0), None],
+            "Distances should be measured from the closest source."
+        );
+    }
+}
@@ -0,0 +1,758 @@
+use crate::Configuration;
+use crate::grid::{Grid, Tile, label_regions};
+use crate::maze::{connect_entrances, has_interior, ring_entrances};
+use crate::rng::Rng;
+use crate::room::{Doorway, Dungeon, Room};
+use crate::vec;
+
+use std::path::Path;
+use std::str::FromStr;
+
+/// A small hand-made room written with the characters of the tiles. The outermost tiles are the
+/// ring around the room: blockers ('%') and doorway anchors ('d'), which give the positions of
+/// the doorways when the prefab is used as a whole room. The tiles inside are room ('_') and wall
+/// ('#') tiles.
+#[derive(Clone, Debug)]
+pub struct Prefab {
+    pub name: String,
+    /// The relative chance of picking the prefab from the library.
+    pub weight: usize,
+    pub tiles: Grid,
+}
+
+/// Returned when a prefab library can not be loaded.
+#[derive(Debug)]
+pub enum PrefabError {
+    Io(std::io::Error),
+    /// A line which is neither a valid header ("prefab <name> [weight]") nor a row of a prefab.
+    Header {
+        line: usize,
+    },
+    /// The rows of the prefab differ in length.
+    Ragged {
+        name: String,
+    },
+    /// A character which is not allowed in prefabs.
+    Character {
+        name: String,
+        character: char,
+    },
+    /// The prefab has no tiles inside its ring.
+    TooSmall {
+        name: String,
+    },
+    /// The ring contains a room or wall tile or the inside contains a blocker or doorway tile.
+    Ring {
+        name: String,
+    },
+    /// The prefab has no anchors, an anchor in a corner or next to another one, or an anchor which
+    /// does not lead to a room tile.
+    Anchors {
+        name: String,
+    },
+    /// The room tiles of the prefab are not connected.
+    Disconnected {
+        name: String,
+    },
+}
+
+impl std::fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrefabError::Io(error) => write!(f, "prefab library could not be read: {error}"),
+            PrefabError::Header { line } => write!(f, "line {line} is not a prefab header or row"),
+            PrefabError::Ragged { name } => write!(f, "rows of prefab {name} differ in length"),
+            PrefabError::Character { name, character } => {
+                write!(f, "prefab {name} contains invalid character {character:?}")
+            }
+            PrefabError::TooSmall { name } => write!(f, "prefab {name} has no room tiles"),
+            PrefabError::Ring { name } => {
+                write!(
+                    f,
+                    "prefab {name} must be surrounded by blockers and anchors"
+                )
+            }
+            PrefabError::Anchors { name } => write!(f, "anchors of prefab {name} are invalid"),
+            PrefabError::Disconnected { name } => {
+                write!(f, "room tiles of prefab {name} are not connected")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrefabError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PrefabError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PrefabError {
+    fn from(value: std::io::Error) -> Self {
+        PrefabError::Io(value)
+    }
+}
+
+/// One of the eight ways to place a prefab: mirrored horizontally or not, then rotated clockwise
+/// by the given number of quarter turns.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Orientation {
+    pub quarter_turns: usize,
+    pub mirrored: bool,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 8] = {
+        let mut result = [Orientation {
+            quarter_turns: 0,
+            mirrored: false,
+        }; 8];
+        let mut index = 0;
+        while index < 8 {
+            result[index] = Orientation {
+                quarter_turns: index % 4,
+                mirrored: index >= 4,
+            };
+            index += 1;
+        }
+        result
+    };
+}
+
+impl Prefab {
+    /// Makes a prefab from its rows and checks that it can be placed without cutting off any of
+    /// its tiles.
+    pub fn new(name: &str, weight: usize, rows: &[&str]) -> Result<Self, PrefabError> {
+        let error_name = || name.to_string();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if rows.iter().any(|row| row.chars().count() != width) {
+            return Err(PrefabError::Ragged { name: error_name() });
+        }
+        if let Some(character) = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .find(|character| !"%d_#".contains(*character))
+        {
+            return Err(PrefabError::Character {
+                name: error_name(),
+                character,
+            });
+        }
+        let height = rows.len();
+        if width < 3 || height < 3 {
+            return Err(PrefabError::TooSmall { name: error_name() });
+        }
+        let tiles = Grid {
+            width,
+            tiles: rows
+                .iter()
+                .flat_map(|row| row.chars())
+                .map(Tile::from)
+                .collect(),
+        };
+
+        let on_ring = |x: usize, y: usize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+        for (index, &tile) in tiles.tiles.iter().enumerate() {
+            let ring_tile = matches!(tile, Tile::Blocker | Tile::Doorway);
+            if ring_tile != on_ring(index % width, index / width) {
+                return Err(PrefabError::Ring { name: error_name() });
+            }
+        }
+
+        let anchors = anchors(&tiles);
+        let valid_anchor = |&(x, y): &(usize, usize)| {
+            if (x == 0 || x == width - 1) && (y == 0 || y == height - 1) {
+                return false;
+            }
+            // The tile inside the ring and the neighbors along the ring.
+            let (inside, along) = if y == 0 || y == height - 1 {
+                let inside = if y == 0 {
+                    width + x
+                } else {
+                    x + (y - 1) * width
+                };
+                (inside, [x - 1 + y * width, x + 1 + y * width])
+            } else {
+                let inside = if x == 0 {
+                    1 + y * width
+                } else {
+                    x - 1 + y * width
+                };
+                (inside, [x + (y - 1) * width, x + (y + 1) * width])
+            };
+            tiles.tiles[inside] == Tile::Room
+                && along.iter().all(|&tile| tiles.tiles[tile] != Tile::Doorway)
+        };
+        if anchors.is_empty() || !anchors.iter().all(valid_anchor) {
+            return Err(PrefabError::Anchors { name: error_name() });
+        }
+
+        let walls: Vec<bool> = (1..height - 1)
+            .flat_map(|y| (1..width - 1).map(move |x| x + y * width))
+            .map(|tile| tiles.tiles[tile] == Tile::Wall)
+            .collect();
+        let (_, sizes) = label_regions(width - 2, height - 2, &walls);
+        if sizes.len() != 1 {
+            return Err(PrefabError::Disconnected { name: error_name() });
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            weight,
+            tiles,
+        })
+    }
+
+    /// Returns the tiles of the prefab placed in the given orientation.
+    pub fn oriented(&self, orientation: Orientation) -> Grid {
+        let mut width = self.tiles.width;
+        let mut tiles = self.tiles.tiles.clone();
+        if orientation.mirrored {
+            for row in tiles.chunks_mut(width) {
+                row.reverse();
+            }
+        }
+        for _ in 0..orientation.quarter_turns % 4 {
+            let height = tiles.len() / width;
+            let mut rotated = tiles.clone();
+            for (index, &tile) in tiles.iter().enumerate() {
+                let (x, y) = (index % width, index / width);
+                rotated[height - 1 - y + x * height] = tile;
+            }
+            tiles = rotated;
+            width = height;
+        }
+        Grid { width, tiles }
+    }
+}
+
+/// Positions of the doorway anchors of the (oriented) prefab tiles.
+fn anchors(tiles: &Grid) -> Vec<(usize, usize)> {
+    (0..tiles.tiles.len())
+        .filter(|&index| tiles.tiles[index] == Tile::Doorway)
+        .map(|index| (index % tiles.width, index / tiles.width))
+        .collect()
+}
+
+/// A collection of prefabs. In text every prefab starts with a header line "prefab <name>
+/// [weight]" (the weight defaults to 1) followed by its rows and ends with an empty line or the
+/// next header. Lines starting with "//" are comments.
+#[derive(Clone, Default, Debug)]
+pub struct PrefabLibrary {
+    pub prefabs: Vec<Prefab>,
+}
+
+impl FromStr for PrefabLibrary {
+    type Err = PrefabError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut prefabs = vec![];
+        let mut current: Option<(&str, usize, Vec<&str>)> = None;
+        let finish = |current: &mut Option<(&str, usize, Vec<&str>)>,
+                      prefabs: &mut Vec<Prefab>|
+         -> Result<(), PrefabError> {
+            if let Some((name, weight, rows)) = current.take() {
+                prefabs.push(Prefab::new(name, weight, &rows)?);
+            }
+            Ok(())
+        };
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with("//") {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix("prefab ") {
+                finish(&mut current, &mut prefabs)?;
+                let error = PrefabError::Header { line: index + 1 };
+                let mut words = header.split_whitespace();
+                let (Some(name), weight, None) = (words.next(), words.next(), words.next()) else {
+                    return Err(error);
+                };
+                let weight = match weight.map(str::parse) {
+                    None => 1,
+                    Some(Ok(weight)) if weight > 0 => weight,
+                    Some(_) => return Err(error),
+                };
+                current = Some((name, weight, vec![]));
+            } else if line.is_empty() {
+                finish(&mut current, &mut prefabs)?;
+            } else if let Some((_, _, rows)) = current.as_mut() {
+                rows.push(line);
+            } else {
+                return Err(PrefabError::Header { line: index + 1 });
+            }
+        }
+        finish(&mut current, &mut prefabs)?;
+        Ok(Self { prefabs })
+    }
+}
+
+impl PrefabLibrary {
+    /// Loads a library from a file or from every file of a directory, in the order of their
+    /// names.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PrefabError> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return std::fs::read_to_string(path)?.parse();
+        }
+        let mut paths = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        let mut result = Self::default();
+        for path in paths.iter().filter(|path| path.is_file()) {
+            let library: Self = std::fs::read_to_string(path)?.parse()?;
+            result.prefabs.extend(library.prefabs);
+        }
+        Ok(result)
+    }
+
+    /// Picks a prefab by weight among those which fit in some orientation, then one of the
+    /// orientations in which it fits.
+    fn pick<R: Rng>(
+        &self,
+        fits: impl Fn(&Grid) -> bool,
+        rng: &mut R,
+    ) -> Option<(usize, Orientation, Grid)> {
+        let candidates: Vec<(usize, Vec<(Orientation, Grid)>)> = self
+            .prefabs
+            .iter()
+            .enumerate()
+            .map(|(index, prefab)| {
+                let orientations = Orientation::ALL
+                    .into_iter()
+                    .map(|orientation| (orientation, prefab.oriented(orientation)))
+                    .filter(|(_, tiles)| fits(tiles))
+                    .collect::<Vec<_>>();
+                (index, orientations)
+            })
+            .filter(|(_, orientations)| !orientations.is_empty())
+            .collect();
+        let total_weight: usize = candidates
+            .iter()
+            .map(|&(index, _)| self.prefabs[index].weight)
+            .sum();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut roll = rng.random_range(0..=total_weight - 1);
+        for (index, mut orientations) in candidates {
+            if roll >= self.prefabs[index].weight {
+                roll -= self.prefabs[index].weight;
+                continue;
+            }
+            let choice = rng.random_range(0..=orientations.len() - 1);
+            let (orientation, tiles) = orientations.swap_remove(choice);
+            return Some((index, orientation, tiles));
+        }
+        None
+    }
+}
+
+/// Parameters of the prefab stamping. Nothing is stamped while the library is empty.
+#[derive(Clone, Debug)]
+pub struct PrefabStamps {
+    pub library: PrefabLibrary,
+    /// What proportion of the generated rooms are replaced by prefab rooms.
+    pub room_chance: f32,
+    /// What proportion of the other rooms get a prefab stamped inside them.
+    pub stamp_chance: f32,
+}
+
+impl Default for PrefabStamps {
+    fn default() -> Self {
+        Self {
+            library: PrefabLibrary::default(),
+            room_chance: 0.1,
+            stamp_chance: 0.2,
+        }
+    }
+}
+
+/// A room of the dungeon which has been replaced by a prefab.
+#[derive(Clone, Copy, Debug)]
+pub struct PrefabRoom {
+    pub room_index: usize,
+    /// Index of the prefab in the library.
+    pub prefab: usize,
+    pub orientation: Orientation,
+}
+
+/// Replaces a proportion of the generated rooms with prefab rooms which fit inside them. The room
+/// shrinks to the dimensions of the inside of the prefab, so it keeps its padding, and its
+/// doorways are replaced by the anchors of the prefab. Must run before the triangulation.
+pub fn make_prefab_rooms<R: Rng>(
+    rng: &mut R,
+    configuration: &Configuration,
+    dungeon: &mut Dungeon,
+) -> Vec<PrefabRoom> {
+    let library = &configuration.prefabs.library;
+    let mut result = vec![];
+    for room_index in 0..dungeon.rooms.len() {
+        if library.prefabs.is_empty() || rng.random_float() >= configuration.prefabs.room_chance {
+            continue;
+        }
+        let bounds = dungeon.rooms[room_index].bounds;
+        let fits =
+            |tiles: &Grid| tiles.width - 2 <= bounds.width && tiles.height() - 2 <= bounds.height;
+        let Some((prefab, orientation, tiles)) = library.pick(fits, rng) else {
+            continue;
+        };
+        let (width, height) = (tiles.width - 2, tiles.height() - 2);
+        let x = bounds.x + rng.random_range(0..=bounds.width - width);
+        let y = bounds.y + rng.random_range(0..=bounds.height - height);
+        dungeon.rooms[room_index].bounds = vec::Rectangle::new(x, y, width, height);
+
+        // The anchors take the place of the old doorways in the list.
+        let anchors = anchors(&tiles)
+            .into_iter()
+            .map(|(anchor_x, anchor_y)| Doorway {
+                room_index,
+                position: vec::vec2u(x + anchor_x - 1, y + anchor_y - 1),
+            });
+        let first = dungeon
+            .doorways
+            .iter()
+            .position(|doorway| doorway.room_index == room_index)
+            .unwrap_or(dungeon.doorways.len());
+        dungeon
+            .doorways
+            .retain(|doorway| doorway.room_index != room_index);
+        dungeon.doorways.splice(first..first, anchors);
+        result.push(PrefabRoom {
+            room_index,
+            prefab,
+            orientation,
+        });
+    }
+    result
+}
+
+/// Places the walls of the tiles at the given grid position. Blockers of the ring become walls as
+/// well when `ring` is set. Only room tiles are changed, so the doorway markers stay open.
+fn place_walls(tiles: &Grid, x: usize, y: usize, ring: bool, grid: &mut Grid) {
+    for (index, &tile) in tiles.tiles.iter().enumerate() {
+        let grid_tile = x + index % tiles.width + (y + index / tiles.width) * grid.width;
+        if (tile == Tile::Wall || (ring && tile == Tile::Blocker))
+            && grid.tiles[grid_tile] == Tile::Room
+        {
+            grid.tiles[grid_tile] = Tile::Wall;
+        }
+    }
+}
+
+/// Connects every entrance of the room to its first room tile, which keeps the doorways which do
+/// not come from anchors (wide doorways and those of the maze filler) open.
+fn connect_room(room: &Room, grid: &mut Grid) {
+    let bounds = room.bounds;
+    let first = (bounds.y..bounds.y + bounds.height)
+        .flat_map(|y| (bounds.x..bounds.x + bounds.width).map(move |x| (x, y)))
+        .map(|(x, y)| x + y * grid.width)
+        .find(|&tile| grid.tiles[tile] != Tile::Wall);
+    let targets: Vec<usize> = first
+        .into_iter()
+        .chain(ring_entrances(grid, room))
+        .collect();
+    connect_entrances(room, &targets, grid);
+}
+
+/// Places the walls of the prefab rooms made by make_prefab_rooms once the grid is carved.
+pub fn stamp_prefab_rooms(
+    configuration: &Configuration,
+    prefab_rooms: &[PrefabRoom],
+    grid: &mut Grid,
+    dungeon: &Dungeon,
+) {
+    for prefab_room in prefab_rooms {
+        let (Some(prefab), Some(room)) = (
+            configuration
+                .prefabs
+                .library
+                .prefabs
+                .get(prefab_room.prefab),
+            dungeon.rooms.get(prefab_room.room_index),
+        ) else {
+            continue;
+        };
+        let tiles = prefab.oriented(prefab_room.orientation);
+        place_walls(&tiles, room.bounds.x - 1, room.bounds.y - 1, false, grid);
+        connect_room(room, grid);
+    }
+}
+
+/// Stamps prefabs inside a proportion of the rooms which have no walls inside yet. The ring of
+/// the prefab becomes walls with openings at the anchors and keeps a tile of space from the sides
+/// of the room, so the stamp can be walked around.
+pub fn stamp_prefabs<R: Rng>(
+    rng: &mut R,
+    configuration: &Configuration,
+    grid: &mut Grid,
+    dungeon: &Dungeon,
+) {
+    let library = &configuration.prefabs.library;
+    for room in &dungeon.rooms {
+        if library.prefabs.is_empty()
+            || has_interior(grid, room)
+            || rng.random_float() >= configuration.prefabs.stamp_chance
+        {
+            continue;
+        }
+        let bounds = room.bounds;
+        let fits =
+            |tiles: &Grid| tiles.width + 2 <= bounds.width && tiles.height() + 2 <= bounds.height;
+        let Some((_, _, tiles)) = library.pick(fits, rng) else {
+            continue;
+        };
+        let height = tiles.height();
+        let x = bounds.x + rng.random_range(1..=bounds.width - 1 - tiles.width);
+        let y = bounds.y + rng.random_range(1..=bounds.height - 1 - height);
+        place_walls(&tiles, x, y, true, grid);
+        connect_room(room, grid);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::make_grid;
+    use crate::mock::{MockMinRng, room};
+    use crate::mst::pick_corridors;
+    use crate::room::generate_rooms;
+    use crate::triangulation::triangulate;
+    use crate::vec::vec2u;
+    use crate::verify::verify;
+
+    use rand::SeedableRng;
+
+    const LIBRARY: &str = include_str!("../prefabs/examples.txt");
+
+    #[test]
+    fn parsing_and_validation() {
+        let library: PrefabLibrary = LIBRARY.parse().expect("Example prefabs should be valid.");
+        assert!(
+            library.prefabs.len() >= 3,
+            "Example library should contain several prefabs."
+        );
+
+        let library: PrefabLibrary = "\
+            // A comment.\n\
+            prefab a 3\n\
+            %d%\n\
+            %_%\n\
+            %%%\n\
+            \n\
+            prefab b\n\
+            %%%%\n\
+            d__%\n\
+            %%%%\n"
+            .parse()
+            .expect("Library should be valid.");
+        let weights: Vec<(&str, usize)> = library
+            .prefabs
+            .iter()
+            .map(|prefab| (prefab.name.as_str(), prefab.weight))
+            .collect();
+        assert_eq!(
+            weights,
+            vec![("a", 3), ("b", 1)],
+            "Names or weights are incorrect."
+        );
+
+        let invalid = [
+            ("%d%\n", PrefabError::Header { line: 1 }),
+            (
+                "prefab a 0\n%d%\n%_%\n%%%\n",
+                PrefabError::Header { line: 1 },
+            ),
+            (
+                "prefab a\n%d%\n%_%%\n%%%\n",
+                PrefabError::Ragged { name: "a".into() },
+            ),
+            (
+                "prefab a\n%d%\n%x%\n%%%\n",
+                PrefabError::Character {
+                    name: "a".into(),
+                    character: 'x',
+                },
+            ),
+            (
+                "prefab a\n%d%\n%%%\n",
+                PrefabError::TooSmall { name: "a".into() },
+            ),
+            (
+                "prefab a\n%d%\n__%\n%%%\n",
+                PrefabError::Ring { name: "a".into() },
+            ),
+            (
+                "prefab a\n%%%\n%_%\n%%%\n",
+                PrefabError::Anchors { name: "a".into() },
+            ),
+            (
+                "prefab a\nd%%\n%_%\n%%%\n",
+                PrefabError::Anchors { name: "a".into() },
+            ),
+            (
+                "prefab a\n%dd%\n%__%\n%%%%\n",
+                PrefabError::Anchors { name: "a".into() },
+            ),
+            (
+                "prefab a\n%d%\n%#%\n%%%\n",
+                PrefabError::Anchors { name: "a".into() },
+            ),
+            (
+                "prefab a\n%d%%\n%_#%\n%#_%\n%%%%\n",
+                PrefabError::Disconnected { name: "a".into() },
+            ),
+        ];
+        for (text, expected) in invalid {
+            let error = text
+                .parse::<PrefabLibrary>()
+                .expect_err("Library should be invalid.");
+            assert_eq!(
+                error.to_string(),
+                expected.to_string(),
+                "Wrong error for {text:?}."
+            );
+        }
+    }
+
+    #[test]
+    fn orientations() {
+        let prefab = Prefab::new("l", 1, &["%%%%", "d_#%", "%__%", "%%%%"])
+            .expect("Prefab should be valid.");
+        let oriented = |quarter_turns, mirrored| {
+            prefab
+                .oriented(Orientation {
+                    quarter_turns,
+                    mirrored,
+                })
+                .to_string()
+        };
+        assert_eq!(
+            oriented(0, false),
+            "%%%%\nd_#%\n%__%\n%%%%\n",
+            "Identity is incorrect."
+        );
+        assert_eq!(
+            oriented(1, false),
+            "%%d%\n%__%\n%_#%\n%%%%\n",
+            "Rotation is incorrect."
+        );
+        assert_eq!(
+            oriented(0, true),
+            "%%%%\n%#_d\n%__%\n%%%%\n",
+            "Mirroring is incorrect."
+        );
+        assert_eq!(
+            oriented(4, false),
+            oriented(0, false),
+            "Four quarter turns should be the identity."
+        );
+        let distinct: std::collections::HashSet<String> = Orientation::ALL
+            .iter()
+            .map(|orientation| prefab.oriented(*orientation).to_string())
+            .collect();
+        assert_eq!(
+            distinct.len(),
+            8,
+            "An asymmetric prefab has eight orientations."
+        );
+    }
+
+    #[test]
+    fn stamping_inside_rooms() {
+        let configuration = Configuration {
+            prefabs: PrefabStamps {
+                library: "prefab pillar\n%d%%\n%_#%\n%__%\n%%%%\n"
+                    .parse()
+                    .expect("Library should be valid."),
+                room_chance: 0.0,
+                stamp_chance: 1.0,
+            },
+            ..Default::default()
+        };
+        let mut grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%\n\
+            %______%\n\
+            %______%\n\
+            %______%\n\
+            %______%\n\
+            %______%\n\
+            %__d___%\n\
+            %%%d%%%%\n",
+        );
+        let dungeon = Dungeon {
+            rooms: vec![room(1, 1, 6, 6)],
+            doorways: vec![],
+        };
+        stamp_prefabs(&mut MockMinRng, &configuration, &mut grid, &dungeon);
+        assert_eq!(
+            grid.to_string(),
+            "\
+            %%%%%%%%\n\
+            %______%\n\
+            %_#_##_%\n\
+            %_#_##_%\n\
+            %_#__#_%\n\
+            %_####_%\n\
+            %__d___%\n\
+            %%%d%%%%\n",
+            "The prefab should be stamped a tile away from the sides."
+        );
+    }
+
+    #[test]
+    fn prefab_rooms_are_connected() {
+        let mut configuration = Configuration::default();
+        configuration.prefabs.library = LIBRARY.parse().expect("Library should be valid.");
+        configuration.prefabs.room_chance = 0.5;
+        configuration.prefabs.stamp_chance = 1.0;
+        let grid_dimensions = vec2u(100, 100);
+        let mut prefab_room_count = 0;
+        for seed in 0..10 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut dungeon = generate_rooms(&configuration, grid_dimensions, Some(20), &mut rng);
+            let prefab_rooms = make_prefab_rooms(&mut rng, &configuration, &mut dungeon);
+            prefab_room_count += prefab_rooms.len();
+            for prefab_room in &prefab_rooms {
+                let bounds = dungeon.rooms[prefab_room.room_index].bounds;
+                let tiles = configuration.prefabs.library.prefabs[prefab_room.prefab]
+                    .oriented(prefab_room.orientation);
+                assert_eq!(
+                    (bounds.width + 2, bounds.height + 2),
+                    (tiles.width, tiles.height()),
+                    "The room should have the dimensions of the prefab."
+                );
+            }
+            let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+            let corridors = pick_corridors(&configuration, &dungeon, &mut triangulation, &mut rng);
+            let (mut grid, _) = make_grid(&configuration, grid_dimensions, &dungeon, &corridors)
+                .expect("The corridors should be carved.");
+            stamp_prefab_rooms(&configuration, &prefab_rooms, &mut grid, &dungeon);
+            stamp_prefabs(&mut rng, &configuration, &mut grid, &dungeon);
+
+            let verification = verify(&grid, &dungeon);
+            assert!(
+                verification.is_valid(&configuration),
+                "Prefabs with seed {seed} have problems: {verification:?}"
+            );
+            for room in &dungeon.rooms {
+                let bounds = room.bounds;
+                let walls: Vec<bool> = grid
+                    .region_tiles(bounds)
+                    .map(|(_, tile)| tile == Tile::Wall)
+                    .collect();
+                let (_, sizes) = label_regions(bounds.width, bounds.height, &walls);
+                assert_eq!(
+                    sizes.len(),
+                    1,
+                    "Prefabs should not cut off a part of the room (seed {seed})."
+                );
+            }
+        }
+        assert!(prefab_room_count > 0, "Some rooms should be prefab rooms.");
+    }
+}
@@ -1,10 +1,34 @@
 use std::ops::RangeInclusive;
 
+/// The number of distinct values random_float can return by default.
+pub const FLOAT_RESOLUTION: usize = 1 << 24;
+
 /// For the purposes of testing the procedures I write will use structures which implement this
 /// wrapper trait. During testing I can then use a mock which implements this traint and returns
 /// specific values.
+///
+/// Only random_range has to be implemented. The other procedures are built on top of it, so a
+/// mock controls them as well: random_float divides a number from 0 to FLOAT_RESOLUTION - 1 by
+/// FLOAT_RESOLUTION and shuffle swaps every element with one picked by random_range.
 pub trait Rng {
     fn random_range(&mut self, range: RangeInclusive<usize>) -> usize;
+
+    /// Returns a number between 0 (inclusive) and 1 (exclusive).
+    fn random_float(&mut self) -> f32 {
+        self.random_range(0..=FLOAT_RESOLUTION - 1) as f32 / FLOAT_RESOLUTION as f32
+    }
+
+    /// Returns true with the given probability.
+    fn random_bool(&mut self, probability: f64) -> bool {
+        (self.random_float() as f64) < probability
+    }
+
+    /// Shuffles the elements in place (Fisher-Yates shuffle).
+    fn shuffle<T>(&mut self, elements: &mut [T]) {
+        for index in (1..elements.len()).rev() {
+            elements.swap(index, self.random_range(0..=index));
+        }
+    }
 }
 
 impl<T> Rng for T
@@ -15,4 +39,64 @@ where
     fn random_range(&mut self, range: RangeInclusive<usize>) -> usize {
         self.random_range(range)
     }
+
+    #[cfg(not(tarpaulin_include))]
+    fn random_float(&mut self) -> f32 {
+        self.random()
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn random_bool(&mut self, probability: f64) -> bool {
+        rand::Rng::random_bool(self, probability.clamp(0.0, 1.0))
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    fn shuffle<E>(&mut self, elements: &mut [E]) {
+        rand::seq::SliceRandom::shuffle(elements, self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{MockMaxRng, MockMinRng, MockRng};
+
+    #[test]
+    fn sampling_with_mocks() {
+        assert_eq!(
+            MockMinRng.random_float(),
+            0.0,
+            "The smallest float should be 0."
+        );
+        assert!(
+            MockMaxRng.random_float() < 1.0,
+            "Floats should be smaller than 1."
+        );
+        let mut rng = MockRng::new(vec![FLOAT_RESOLUTION / 4, FLOAT_RESOLUTION / 2]);
+        assert_eq!(rng.random_float(), 0.25, "Float is incorrect.");
+        assert!(!rng.random_bool(0.5), "0.5 is not smaller than 0.5.");
+        assert!(
+            MockMinRng.random_bool(0.01),
+            "0 is smaller than any probability."
+        );
+        assert!(
+            !MockMinRng.random_bool(0.0),
+            "Probability 0 should never be true."
+        );
+        assert!(
+            MockMaxRng.random_bool(1.0),
+            "Probability 1 should always be true."
+        );
+    }
+
+    #[test]
+    fn shuffling_with_mocks() {
+        let mut elements = [0, 1, 2, 3];
+        // Swaps every element with itself.
+        MockMaxRng.shuffle(&mut elements);
+        assert_eq!(elements, [0, 1, 2, 3], "Elements should stay in place.");
+        // Swaps the last element with the first one, then the new last one with the first one...
+        MockMinRng.shuffle(&mut elements);
+        assert_eq!(elements, [1, 2, 3, 0], "Elements should rotate.");
+    }
 }
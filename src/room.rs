@@ -1,3 +1,4 @@
+use crate::maze::MazeAlgorithm;
 use crate::vec::{self, Rectangle, Vector2};
 use crate::{Configuration, rng::Rng};
 
@@ -34,6 +35,29 @@ pub struct Dungeon {
 
 pub type Edges = Vec<(usize, usize)>;
 
+/// A class of rooms, given by their dimensions, with its own limits on the number of corridors
+/// and optionally its own maze algorithm.
+#[derive(Clone, Debug)]
+pub struct RoomArchetype {
+    /// The minimum room dimensions required for a room to belong to the archetype.
+    pub min_dimension: usize,
+    /// The maximum room dimensions required for a room to belong to the archetype.
+    pub max_dimension: usize,
+    /// The minimum and maximum number of corridors connected to rooms of this archetype.
+    pub degree: (usize, usize),
+    /// The algorithm which carves the mazes of rooms of this archetype. Uses the global algorithm
+    /// when not set.
+    pub maze_algorithm: Option<MazeAlgorithm>,
+}
+
+impl RoomArchetype {
+    /// Answers whether both dimensions of the room are within the range of the archetype.
+    pub fn matches(&self, room: &Room) -> bool {
+        let dimensions = self.min_dimension..=self.max_dimension;
+        dimensions.contains(&room.bounds.width) && dimensions.contains(&room.bounds.height)
+    }
+}
+
 // #[derive(Clone, Debug)]
 // pub struct RoomGraph {
 //     pub rooms: Vec<Room>,
@@ -284,8 +308,8 @@ mod test {
         );
         assert_eq!(result.rooms.len(), 1, "There should be exactly 1 room.");
         assert!(
-            result.rooms[0].bounds.width as usize == configuration.min_room_dimension
-                && result.rooms[0].bounds.height as usize == configuration.min_room_dimension,
+            result.rooms[0].bounds.width == configuration.min_room_dimension
+                && result.rooms[0].bounds.height == configuration.min_room_dimension,
             "The room dimensions are not correct."
         );
     }
@@ -14,7 +14,7 @@ pub fn vec2u(x: usize, y: usize) -> Vector2 {
     }
 }
 
-#[derive(Clone, Copy, Add, AddAssign, Sub, SubAssign, Default, Debug)]
+#[derive(Clone, Copy, Add, AddAssign, Sub, SubAssign, Default, PartialEq, Eq, Debug)]
 pub struct Vector2 {
     pub x: i32,
     pub y: i32,
@@ -0,0 +1,315 @@
+use crate::Configuration;
+use crate::grid::{Grid, Tile, label_regions};
+use crate::room::Dungeon;
+use crate::vec;
+
+/// Problems found in a generated grid. Tiles are given as indices in the grid and doorways as
+/// indices in the doorway array of the dungeon.
+#[derive(Clone, Default, Debug)]
+pub struct Verification {
+    /// Rooms which cannot be reached from the first room.
+    pub unreachable_rooms: Vec<usize>,
+    /// Doorways which have been placed in the grid, but have no corridor leading out of them.
+    pub dead_end_doorways: Vec<usize>,
+    /// Doorways which cannot be reached from the first placed doorway of the same room, which
+    /// happens when a maze cuts the room in separate parts.
+    pub cut_off_doorways: Vec<usize>,
+    /// North-west tiles of 2x2 squares made of corridor tiles.
+    pub corridor_squares: Vec<usize>,
+}
+
+impl Verification {
+    /// Answers whether the grid has no problems. Corridor squares are a problem only when the
+    /// configuration disallows them and all corridors are one tile wide.
+    pub fn is_valid(&self, configuration: &Configuration) -> bool {
+        let wide_corridors = configuration.corridor_width > 1
+            || configuration
+                .reintroduced_corridor_width
+                .is_some_and(|width| width > 1);
+        self.unreachable_rooms.is_empty()
+            && self.dead_end_doorways.is_empty()
+            && self.cut_off_doorways.is_empty()
+            && (!configuration.disallow_corridor_squares
+                || wide_corridors
+                || self.corridor_squares.is_empty())
+    }
+}
+
+/// Answers whether one can walk on the tile.
+fn is_walkable(tile: Tile) -> bool {
+    matches!(tile, Tile::Room | Tile::Doorway | Tile::Corridor)
+}
+
+/// Returns the neighbors of a tile which are inside the grid.
+fn neighbors(grid: &Grid, tile: usize) -> impl Iterator<Item = usize> {
+    let width = grid.width;
+    let x = tile % width;
+    let y = tile / width;
+    [
+        (y + 1 < grid.height(), tile + width),
+        (y > 0, tile.wrapping_sub(width)),
+        (x + 1 < width, tile + 1),
+        (x > 0, tile.wrapping_sub(1)),
+    ]
+    .into_iter()
+    .filter_map(|(valid, neighbor)| valid.then_some(neighbor))
+}
+
+/// Checks that every room of the generated grid can be reached from every other room by flood
+/// filling the walkable tiles (rooms, doorways and corridors). Also finds doorways which lead
+/// nowhere or which a maze has cut off from the rest of their room and 2x2 squares of corridors.
+pub fn verify(grid: &Grid, dungeon: &Dungeon) -> Verification {
+    use Tile::*;
+
+    let mut result = Verification::default();
+    let width = grid.width;
+    let height = grid.height();
+    let walls: Vec<bool> = grid.tiles.iter().map(|&tile| !is_walkable(tile)).collect();
+    let (labels, _) = label_regions(width, height, &walls);
+
+    // The region through which each room is entered and the region inside the room which is
+    // reached through its first doorway. The regions inside the rooms are labeled with indices
+    // local to the room.
+    let mut room_regions: Vec<Option<usize>> = vec![None; dungeon.rooms.len()];
+    let mut interior_regions: Vec<Option<usize>> = vec![None; dungeon.rooms.len()];
+    let interior_labels: Vec<Vec<usize>> = dungeon
+        .rooms
+        .iter()
+        .map(|room| {
+            let walls: Vec<bool> = grid
+                .region_tiles(room.bounds)
+                .map(|(_, tile)| !is_walkable(tile))
+                .collect();
+            label_regions(room.bounds.width, room.bounds.height, &walls).0
+        })
+        .collect();
+
+    for (doorway_index, doorway) in dungeon.doorways.iter().enumerate() {
+        let position = vec::to_index(doorway.position, width);
+        if grid.tiles[position] != Doorway {
+            // The doorway has not been used by any corridor.
+            continue;
+        }
+
+        if !neighbors(grid, position).any(|neighbor| grid.tiles[neighbor] == Corridor) {
+            result.dead_end_doorways.push(doorway_index);
+        }
+
+        let Some(room) = dungeon.rooms.get(doorway.room_index) else {
+            continue;
+        };
+        room_regions[doorway.room_index].get_or_insert(labels[position]);
+
+        // The doorway is outside the room, so find the label of the tile on the other side.
+        let bounds = room.bounds;
+        let interior_region = neighbors(grid, position)
+            .find(|&neighbor| {
+                bounds.check_collision_point_rec(vec::vec2u(neighbor % width, neighbor / width))
+            })
+            .map(|neighbor| {
+                let local =
+                    neighbor % width - bounds.x + (neighbor / width - bounds.y) * bounds.width;
+                interior_labels[doorway.room_index][local]
+            })
+            .filter(|&label| label != usize::MAX);
+        match (interior_regions[doorway.room_index], interior_region) {
+            (_, None) => result.cut_off_doorways.push(doorway_index),
+            (None, region) => interior_regions[doorway.room_index] = region,
+            (Some(first), Some(region)) if first != region => {
+                result.cut_off_doorways.push(doorway_index)
+            }
+            _ => {}
+        }
+    }
+
+    // Rooms without placed doorways are reachable only when they are alone.
+    let first_region = room_regions.first().copied().flatten();
+    for (room_index, region) in room_regions.iter().enumerate().skip(1) {
+        if region.is_none() || *region != first_region {
+            result.unreachable_rooms.push(room_index);
+        }
+    }
+
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width - 1 {
+            let northwest = x + y * width;
+            if [
+                northwest,
+                northwest + 1,
+                northwest + width,
+                northwest + width + 1,
+            ]
+            .iter()
+            .all(|&tile| grid.tiles[tile] == Corridor)
+            {
+                result.corridor_squares.push(northwest);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::make_grid;
+    use crate::mock::{doorway, room};
+    use crate::vec::{to_index, vec2u};
+
+    use rand::SeedableRng;
+
+    fn two_rooms() -> Dungeon {
+        Dungeon {
+            rooms: vec![room(1, 1, 3, 3), room(7, 1, 3, 3)],
+            doorways: vec![doorway(4, 2, 0), doorway(6, 2, 1)],
+        }
+    }
+
+    #[test]
+    fn connected_rooms() {
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %___%@%___%\n\
+            %___dcd___%\n\
+            %___%@%___%\n\
+            %%%%%%%%%%%\n",
+        );
+        let verification = verify(&grid, &two_rooms());
+        assert!(
+            verification.is_valid(&Configuration::default()),
+            "The grid should have no problems: {verification:?}"
+        );
+    }
+
+    #[test]
+    fn unreachable_room_and_dead_end() {
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %___%@%___%\n\
+            %___dc%___%\n\
+            %___%@%___%\n\
+            %%%%%%%%%%%\n",
+        );
+        let verification = verify(&grid, &two_rooms());
+        assert_eq!(
+            &verification.unreachable_rooms,
+            &[1],
+            "The second room should be unreachable."
+        );
+        assert!(
+            verification.dead_end_doorways.is_empty(),
+            "The first doorway leads to a corridor."
+        );
+
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %___%#%___%\n\
+            %___d#d___%\n\
+            %___%#%___%\n\
+            %%%%%%%%%%%\n",
+        );
+        let verification = verify(&grid, &two_rooms());
+        assert_eq!(
+            &verification.dead_end_doorways,
+            &[0, 1],
+            "Both doorways lead nowhere."
+        );
+    }
+
+    #[test]
+    fn maze_cuts_off_doorway() {
+        let dungeon = Dungeon {
+            rooms: vec![room(1, 1, 3, 3), room(7, 1, 3, 3)],
+            doorways: vec![doorway(4, 2, 0), doorway(2, 4, 0), doorway(6, 2, 1)],
+        };
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%%%%%%\n\
+            %_#_%@%___%\n\
+            %_#_dcd___%\n\
+            %_#_%c%___%\n\
+            %%d%%c%%%%%\n\
+            %#cccc@###%\n\
+            %%%%%%%%%%%\n",
+        );
+        let verification = verify(&grid, &dungeon);
+        assert_eq!(
+            &verification.cut_off_doorways,
+            &[1],
+            "The maze separates the second doorway from the first one."
+        );
+    }
+
+    #[test]
+    fn corridor_squares() {
+        let grid = Grid::parse_lenient(
+            "\
+            %%%%%%\n\
+            %#cc#%\n\
+            %#cc#%\n\
+            %%%%%%\n",
+        );
+        let dungeon = Dungeon {
+            rooms: vec![],
+            doorways: vec![],
+        };
+        let verification = verify(&grid, &dungeon);
+        assert_eq!(
+            &verification.corridor_squares,
+            &[to_index(vec2u(2, 1), grid.width)],
+            "There should be a single corridor square."
+        );
+        assert!(
+            !verification.is_valid(&Configuration::default()),
+            "Corridor squares are not allowed by default."
+        );
+        let configuration = Configuration {
+            disallow_corridor_squares: false,
+            ..Default::default()
+        };
+        assert!(
+            verification.is_valid(&configuration),
+            "Corridor squares should be allowed."
+        );
+    }
+
+    #[test]
+    fn generated_grids_are_valid() {
+        use crate::{mst::pick_corridors, room::generate_rooms, triangulation::triangulate};
+
+        let diagonal_configuration = Configuration {
+            diagonal_corridors: true,
+            ..Default::default()
+        };
+        let wide_configuration = Configuration {
+            corridor_width: 3,
+            reintroduced_corridor_width: Some(1),
+            ..Default::default()
+        };
+        let grid_dimensions = vec2u(100, 100);
+        for (configuration, seed) in [
+            Configuration::default(),
+            diagonal_configuration,
+            wide_configuration,
+        ]
+        .iter()
+        .flat_map(|configuration| (0..10).map(move |seed| (configuration, seed)))
+        {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut dungeon = generate_rooms(configuration, grid_dimensions, Some(20), &mut rng);
+            let mut triangulation = triangulate(grid_dimensions, &mut dungeon);
+            let corridors = pick_corridors(configuration, &dungeon, &mut triangulation, &mut rng);
+            let (grid, _) = make_grid(configuration, grid_dimensions, &dungeon, &corridors)
+                .expect("The corridors should be carved.");
+            let verification = verify(&grid, &dungeon);
+            assert!(
+                verification.is_valid(configuration),
+                "Generated grid with seed {seed} has problems: {verification:?}"
+            );
+        }
+    }
+}